@@ -3,6 +3,8 @@ use std::path::PathBuf;
 
 use clap::Parser;
 
+use crate::service::deploy::SuspensionPolicy;
+
 #[derive(Parser)]
 #[command(author, version, about, long_about)]
 pub(super) struct DoubleBlindArgs {
@@ -13,6 +15,9 @@ pub(super) struct DoubleBlindArgs {
     default_value = "127.0.0.1:8080"
   )]
   pub(super) listen_addr: SocketAddr,
+  /// serves the deployed sites on this address if set
+  #[arg(long, env = "DOUBLEBLIND_STATIC_LISTEN_ADDR")]
+  pub(super) static_listen_addr: Option<SocketAddr>,
   #[arg(long, env = "DOUBLEBLIND_POSTGRES_HOST")]
   pub(super) database_host: String,
   #[arg(long, env = "DOUBLEBLIND_POSTGRES_USERNAME")]
@@ -27,8 +32,37 @@ pub(super) struct DoubleBlindArgs {
   pub(super) website_path: PathBuf,
   #[arg(long, env = "DOUBLEBLIND_WEBSITE_DOMAIN")]
   pub(super) website_domain: String,
+  /// name server for verifying custom domains, the ones of the system are used if unset
+  #[arg(long, env = "DOUBLEBLIND_DNS_RESOLVER")]
+  pub(super) dns_resolver: Option<SocketAddr>,
   #[arg(long, env = "DOUBLEBLIND_GITHUB_HMAC_SECRET_PATH")]
   pub(super) github_hmac_secret_file: PathBuf,
   #[arg(long, env = "DOUBLEBLIND_GITHUB_PRIVATE_KEY_PATH")]
   pub(super) github_secret_key_file: PathBuf,
+  /// secret signing the cookies of reviewers, access codes are disabled if unset
+  #[arg(long, env = "DOUBLEBLIND_ACCESS_SECRET_PATH")]
+  pub(super) access_secret_file: Option<PathBuf>,
+  /// token for the admin endpoints, they are disabled if unset
+  #[arg(long, env = "DOUBLEBLIND_ADMIN_TOKEN_PATH")]
+  pub(super) admin_token_file: Option<PathBuf>,
+  #[arg(long, env = "DOUBLEBLIND_DEPLOYMENT_HISTORY", default_value = "5")]
+  pub(super) deployment_history: u64,
+  #[arg(long, env = "DOUBLEBLIND_DEPLOYMENT_WORKERS", default_value = "4")]
+  pub(super) deployment_workers: usize,
+  #[arg(long, env = "DOUBLEBLIND_MAX_SITE_SIZE", default_value = "1073741824")]
+  pub(super) max_site_size: u64,
+  #[arg(long, env = "DOUBLEBLIND_MAX_SITE_FILES", default_value = "100000")]
+  pub(super) max_site_files: u64,
+  #[arg(long, env = "DOUBLEBLIND_MAX_FILE_SIZE", default_value = "134217728")]
+  pub(super) max_file_size: u64,
+  #[arg(long, env = "DOUBLEBLIND_DEFAULT_QUOTA", default_value = "2147483648")]
+  pub(super) default_quota: u64,
+  /// what happens to the sites of suspended or uninstalled github app installations
+  #[arg(
+    long,
+    env = "DOUBLEBLIND_SUSPENSION_POLICY",
+    value_enum,
+    default_value = "unpublish"
+  )]
+  pub(super) suspension_policy: SuspensionPolicy,
 }
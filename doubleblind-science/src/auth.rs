@@ -2,16 +2,26 @@ use std::str::FromStr;
 use std::sync::Arc;
 
 use axum::async_trait;
-use axum::extract::FromRequestParts;
+use axum::body::Body;
+use axum::extract::{FromRequest, FromRequestParts};
 use axum::http::request::Parts;
+use axum::http::{header, HeaderValue, Request};
 use axum_extra::extract::CookieJar;
+use bytes::Bytes;
+use hmac::{Hmac, Mac};
 use reqwest::StatusCode;
-use tracing::error;
+use serde::de::DeserializeOwned;
+use sha2::{Digest, Sha256};
+use tracing::{error, info};
 use uuid::Uuid;
 
 use crate::state::DoubleBlindState;
 
 pub(crate) const SESSION_COOKIE: &str = "session_id";
+/// header github puts the hmac of a webhook payload into
+const SIGNATURE_HEADER: &str = "X-Hub-Signature-256";
+const SIGNATURE_PREFIX: &str = "sha256=";
+const BEARER_PREFIX: &str = "Bearer ";
 
 #[derive(Debug, Clone)]
 pub(crate) struct SessionData {
@@ -46,3 +56,175 @@ impl FromRequestParts<DoubleBlindState> for Session {
     Ok(Self(data))
   }
 }
+
+/// Operator of the instance, authenticated with the configured admin token.
+pub(crate) struct Admin;
+
+#[async_trait]
+impl FromRequestParts<DoubleBlindState> for Admin {
+  type Rejection = StatusCode;
+
+  async fn from_request_parts(
+    parts: &mut Parts,
+    state: &DoubleBlindState,
+  ) -> Result<Self, Self::Rejection> {
+    // without a token the admin endpoints do not exist
+    let token = state.admin_token.as_ref().ok_or(StatusCode::NOT_FOUND)?;
+
+    verify_admin_token(token, parts.headers.get(header::AUTHORIZATION))?;
+
+    Ok(Self)
+  }
+}
+
+/// Checks the `Bearer <token>` authorization header, only digests of the tokens are compared so
+/// that the time it takes does not tell how much of it was right.
+fn verify_admin_token(token: &str, authorization: Option<&HeaderValue>) -> Result<(), StatusCode> {
+  let given = authorization
+    .and_then(|value| value.to_str().ok())
+    .and_then(|value| value.strip_prefix(BEARER_PREFIX))
+    .ok_or(StatusCode::UNAUTHORIZED)?;
+
+  if Sha256::digest(given.as_bytes()) != Sha256::digest(token.as_bytes()) {
+    error!("wrong admin token");
+    return Err(StatusCode::FORBIDDEN);
+  }
+
+  Ok(())
+}
+
+/// Payload of a github webhook, only extracted if it is signed with the secret of the github app.
+pub(crate) struct GithubWebhook<T>(pub T);
+
+#[async_trait]
+impl<T: DeserializeOwned + Send> FromRequest<DoubleBlindState, Body> for GithubWebhook<T> {
+  type Rejection = StatusCode;
+
+  async fn from_request(
+    req: Request<Body>,
+    state: &DoubleBlindState,
+  ) -> Result<Self, Self::Rejection> {
+    let signature = req.headers().get(SIGNATURE_HEADER).cloned();
+    let body = Bytes::from_request(req, state).await.map_err(|e| {
+      error!("cannot read webhook body {e}");
+      StatusCode::BAD_REQUEST
+    })?;
+
+    verify_signature(
+      state.github_hmac_secret.as_bytes(),
+      signature.as_ref(),
+      &body,
+    )?;
+
+    let payload = serde_json::from_slice(&body).map_err(|e| {
+      error!("cannot parse webhook body from github {e}");
+      StatusCode::BAD_REQUEST
+    })?;
+
+    Ok(Self(payload))
+  }
+}
+
+/// Checks the `sha256=<hex>` signature github sends along with every webhook, the comparison takes
+/// constant time.
+fn verify_signature(
+  secret: &[u8],
+  signature: Option<&HeaderValue>,
+  body: &[u8],
+) -> Result<(), StatusCode> {
+  // everybody could sign payloads with an empty key
+  if secret.is_empty() {
+    error!("no github webhook secret configured, rejecting webhook");
+    return Err(StatusCode::INTERNAL_SERVER_ERROR);
+  }
+
+  let signature = signature.ok_or_else(|| {
+    info!("github webhook without {SIGNATURE_HEADER}");
+    StatusCode::UNAUTHORIZED
+  })?;
+
+  let signature = signature
+    .to_str()
+    .ok()
+    .and_then(|value| value.strip_prefix(SIGNATURE_PREFIX))
+    .and_then(|value| hex::decode(value).ok())
+    .ok_or_else(|| {
+      info!("{SIGNATURE_HEADER} is not of the form {SIGNATURE_PREFIX}<hex>");
+      StatusCode::BAD_REQUEST
+    })?;
+
+  let mut mac = Hmac::<Sha256>::new_from_slice(secret).expect("hmac accepts keys of any size");
+  mac.update(body);
+  mac.verify_slice(&signature).map_err(|_| {
+    error!("non github entity tried to call a webhook endpoint!");
+    StatusCode::FORBIDDEN
+  })
+}
+
+#[cfg(test)]
+mod tests {
+  use axum::http::HeaderValue;
+  use hmac::{Hmac, Mac};
+  use reqwest::StatusCode;
+  use sha2::Sha256;
+
+  use crate::auth::{verify_admin_token, verify_signature};
+
+  #[test]
+  fn test_verifies_signature() {
+    let body = br#"{"zen":"Keep it logically awesome."}"#;
+    let mut mac = Hmac::<Sha256>::new_from_slice(b"secret").unwrap();
+    mac.update(body);
+    let signature = hex::encode(mac.finalize().into_bytes());
+    let header = |value: &str| HeaderValue::from_str(value).unwrap();
+    let signed = header(&format!("sha256={signature}"));
+
+    assert_eq!(verify_signature(b"secret", Some(&signed), body), Ok(()));
+    assert_eq!(
+      verify_signature(b"other", Some(&signed), body),
+      Err(StatusCode::FORBIDDEN)
+    );
+    assert_eq!(
+      verify_signature(b"secret", Some(&signed), b"{}"),
+      Err(StatusCode::FORBIDDEN)
+    );
+    assert_eq!(
+      verify_signature(b"secret", Some(&header(&signature)), body),
+      Err(StatusCode::BAD_REQUEST)
+    );
+    assert_eq!(
+      verify_signature(b"secret", Some(&header("sha256")), body),
+      Err(StatusCode::BAD_REQUEST)
+    );
+    assert_eq!(
+      verify_signature(b"secret", None, body),
+      Err(StatusCode::UNAUTHORIZED)
+    );
+    assert_eq!(
+      verify_signature(b"", Some(&signed), body),
+      Err(StatusCode::INTERNAL_SERVER_ERROR)
+    );
+  }
+
+  #[test]
+  fn test_verifies_admin_token() {
+    let header = |value: &str| HeaderValue::from_str(value).unwrap();
+
+    assert_eq!(
+      verify_admin_token("token", Some(&header("Bearer token"))),
+      Ok(())
+    );
+    assert_eq!(
+      verify_admin_token("token", Some(&header("Bearer other"))),
+      Err(StatusCode::FORBIDDEN)
+    );
+    assert_eq!(
+      verify_admin_token("token", Some(&header("token"))),
+      Err(StatusCode::UNAUTHORIZED)
+    );
+    assert_eq!(
+      verify_admin_token("token", None),
+      Err(StatusCode::UNAUTHORIZED)
+    );
+  }
+}
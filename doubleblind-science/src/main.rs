@@ -11,7 +11,8 @@ use tracing::{error, info, Level, Span};
 use tracing_subscriber::FmtSubscriber;
 
 use crate::args::DoubleBlindArgs;
-use crate::routes::route;
+use crate::routes::{route, static_route};
+use crate::service::extract::ExtractionLimits;
 use crate::state::DoubleBlindState;
 
 mod args;
@@ -50,13 +51,45 @@ async fn main() -> anyhow::Result<()> {
     &args.github_client_id,
     &args.website_path,
     &args.website_domain,
+    args.dns_resolver,
     &args.github_hmac_secret_file,
     &args.github_secret_key_file,
+    args.access_secret_file.as_deref(),
+    args.admin_token_file.as_deref(),
+    args.deployment_history,
+    ExtractionLimits {
+      max_total_size: args.max_site_size,
+      max_files: args.max_site_files,
+      max_file_size: args.max_file_size,
+    },
+    args.default_quota,
+    args.suspension_policy,
   )
   .await;
 
   let deployment_service_copy = state.deployment_service.clone();
-  let deploy_loop_future = deployment_service_copy.deploy_loop();
+  let requeued = deployment_service_copy.requeue_interrupted().await?;
+  if requeued > 0 {
+    info!("Resuming {} interrupted deployments", requeued);
+  }
+  let deploy_loop_future = deployment_service_copy.deploy_workers(args.deployment_workers);
+  let expiry_service_copy = state.expiry_service.clone();
+  let expiry_loop_future = expiry_service_copy.expiry_loop();
+
+  // without a separate web server the sites are served by a second listener
+  let static_state = state.clone();
+  let static_listen_addr = args.static_listen_addr;
+  let static_server = async move {
+    match static_listen_addr {
+      Some(addr) => {
+        let server =
+          Server::bind(&addr).serve(static_route().with_state(static_state).into_make_service());
+        info!("Serving sites on http://{}...", server.local_addr());
+        server.await
+      }
+      None => std::future::pending().await,
+    }
+  };
 
   let router = route()
     .layer(cors)
@@ -99,6 +132,16 @@ async fn main() -> anyhow::Result<()> {
         error!("Error while serving api: {}", e);
       }
     }
+    result = expiry_loop_future => {
+      if let Err(e) = result {
+        error!("Error while expiring sites: {}", e);
+      }
+    }
+    result = static_server => {
+      if let Err(e) = result {
+        error!("Error while serving sites: {}", e);
+      }
+    }
   }
 
   Ok(())
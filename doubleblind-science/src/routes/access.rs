@@ -0,0 +1,131 @@
+use axum::extract::{Form, State};
+use axum::http::{header, HeaderMap, StatusCode};
+use axum::response::{Html, Redirect};
+use axum_extra::extract::{
+  cookie::{Cookie, SameSite},
+  CookieJar,
+};
+use serde::Deserialize;
+use tracing::{error, info};
+
+use crate::service::access::{SiteAccess, ACCESS_COOKIE, ACCESS_LIFETIME};
+use crate::state::DoubleBlindState;
+
+#[derive(Deserialize)]
+pub(super) struct AccessForm {
+  code: String,
+}
+
+fn access_page(error: Option<&str>) -> Html<String> {
+  let error = error
+    .map(|error| format!("<p style=\"color:#b00\">{error}</p>"))
+    .unwrap_or_default();
+
+  Html(format!(
+    "<!DOCTYPE html>\n<html>\n<head>\n<meta charset=\"utf-8\">\n\
+     <meta name=\"robots\" content=\"noindex\">\n<title>Access code required</title>\n</head>\n\
+     <body style=\"font-family:sans-serif;max-width:30em;margin:4em auto\">\n\
+     <h1>Access code required</h1>\n<p>This site is only visible to its reviewers.</p>\n{error}\
+     <form method=\"post\" action=\"/.doubleblind/access\">\n\
+     <input name=\"code\" autocomplete=\"off\" autofocus required>\n\
+     <button type=\"submit\">Continue</button>\n</form>\n</body>\n</html>\n"
+  ))
+}
+
+/// Subdomain of the site the request was made for, nginx has to pass the original host along.
+pub(super) async fn site_domain(
+  state: &DoubleBlindState,
+  headers: &HeaderMap,
+) -> anyhow::Result<Option<String>> {
+  match headers
+    .get(header::HOST)
+    .and_then(|host| host.to_str().ok())
+  {
+    Some(host) => state.access_service.site_domain(host).await,
+    None => Ok(None),
+  }
+}
+
+/// Subrequest made by nginx before it delivers any file of a site. nginx treats everything but 2xx,
+/// 401 and 403 as an error, so unknown and hidden sites are answered with 403.
+pub(super) async fn site_access_check(
+  State(state): State<DoubleBlindState>,
+  headers: HeaderMap,
+  jar: CookieJar,
+) -> StatusCode {
+  let domain = match site_domain(&state, &headers).await {
+    Ok(Some(value)) => value,
+    Ok(None) => return StatusCode::FORBIDDEN,
+    Err(e) => {
+      error!("error while looking up site {e}");
+      return StatusCode::INTERNAL_SERVER_ERROR;
+    }
+  };
+
+  match state
+    .access_service
+    .authorize(&domain, jar.get(ACCESS_COOKIE).map(|cookie| cookie.value()))
+    .await
+  {
+    Ok(SiteAccess::Granted(_)) => StatusCode::NO_CONTENT,
+    Ok(SiteAccess::CodeRequired) => StatusCode::UNAUTHORIZED,
+    Ok(SiteAccess::Hidden) => StatusCode::FORBIDDEN,
+    Err(e) => {
+      error!("error while checking access to {domain} {e}");
+      StatusCode::INTERNAL_SERVER_ERROR
+    }
+  }
+}
+
+pub(super) async fn site_access_page() -> (StatusCode, Html<String>) {
+  (StatusCode::UNAUTHORIZED, access_page(None))
+}
+
+/// Exchanges a valid code for a cookie which is only sent to the subdomain of the site.
+pub(super) async fn site_access_redeem(
+  State(state): State<DoubleBlindState>,
+  headers: HeaderMap,
+  jar: CookieJar,
+  Form(form): Form<AccessForm>,
+) -> Result<(CookieJar, Redirect), (StatusCode, Html<String>)> {
+  let domain = match site_domain(&state, &headers).await {
+    Ok(Some(value)) => value,
+    Ok(None) => return Err((StatusCode::NOT_FOUND, access_page(Some("Unknown site.")))),
+    Err(e) => {
+      error!("error while looking up site {e}");
+      return Err((
+        StatusCode::INTERNAL_SERVER_ERROR,
+        access_page(Some("Something went wrong, please try again.")),
+      ));
+    }
+  };
+
+  let value = match state.access_service.redeem(&domain, &form.code).await {
+    Ok(Some(value)) => value,
+    Ok(None) => {
+      info!("rejecting access code for {domain}");
+      return Err((
+        StatusCode::UNAUTHORIZED,
+        access_page(Some("This code is not valid.")),
+      ));
+    }
+    Err(e) => {
+      error!("error while redeeming access code for {domain} {e}");
+      return Err((
+        StatusCode::INTERNAL_SERVER_ERROR,
+        access_page(Some("Something went wrong, please try again.")),
+      ));
+    }
+  };
+
+  // without a domain attribute the cookie stays on the subdomain of the site
+  let access_cookie = Cookie::build(ACCESS_COOKIE, value)
+    .same_site(SameSite::Lax)
+    .path("/")
+    .secure(true)
+    .http_only(true)
+    .max_age(ACCESS_LIFETIME)
+    .finish();
+
+  Ok((jar.add(access_cookie), Redirect::to("/")))
+}
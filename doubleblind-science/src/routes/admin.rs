@@ -0,0 +1,131 @@
+use axum::extract::{Path, Query, State};
+use axum::http::StatusCode;
+use axum::Json;
+use serde::{Deserialize, Serialize};
+use time::OffsetDateTime;
+use tracing::{error, info};
+
+use entity::webhook_delivery;
+
+use crate::auth::Admin;
+use crate::state::DoubleBlindState;
+
+/// number of deliveries listed if the request does not ask for a different amount
+const DEFAULT_DELIVERIES: u64 = 50;
+const MAX_DELIVERIES: u64 = 500;
+
+#[derive(Deserialize)]
+pub(super) struct DeliveryQuery {
+  limit: Option<u64>,
+}
+
+#[derive(Deserialize)]
+pub(super) struct QuotaSettings {
+  /// bytes each repository of the installation may occupy, the default quota applies if unset
+  quota: Option<u64>,
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "snake_case")]
+pub(super) enum DeliveryOutcome {
+  Processing,
+  Processed,
+  Ignored,
+  Failed,
+}
+
+#[derive(Serialize)]
+pub(super) struct FrontendDelivery {
+  id: String,
+  event: String,
+  action: Option<String>,
+  outcome: DeliveryOutcome,
+  status: Option<i32>,
+  attempts: i32,
+  /// retries and redeliveries which were acknowledged without processing them again
+  duplicates: i32,
+  #[serde(with = "time::serde::rfc3339")]
+  received_at: OffsetDateTime,
+  #[serde(with = "time::serde::rfc3339::option")]
+  finished_at: Option<OffsetDateTime>,
+}
+
+impl From<webhook_delivery::Model> for FrontendDelivery {
+  fn from(value: webhook_delivery::Model) -> Self {
+    let outcome = match value.status {
+      None => DeliveryOutcome::Processing,
+      Some(204) => DeliveryOutcome::Ignored,
+      Some(200..=299) => DeliveryOutcome::Processed,
+      Some(_) => DeliveryOutcome::Failed,
+    };
+
+    FrontendDelivery {
+      id: value.id,
+      event: value.event,
+      action: value.action,
+      outcome,
+      status: value.status,
+      attempts: value.attempts,
+      duplicates: value.duplicates,
+      received_at: value.received_at,
+      finished_at: value.finished_at,
+    }
+  }
+}
+
+/// Lists the latest webhook deliveries of github and how they were handled.
+pub(super) async fn admin_deliveries(
+  _admin: Admin,
+  State(state): State<DoubleBlindState>,
+  Query(query): Query<DeliveryQuery>,
+) -> Result<Json<Vec<FrontendDelivery>>, StatusCode> {
+  let limit = query
+    .limit
+    .unwrap_or(DEFAULT_DELIVERIES)
+    .clamp(1, MAX_DELIVERIES);
+
+  match state.delivery_service.recent(limit).await {
+    Ok(deliveries) => Ok(Json(
+      deliveries.into_iter().map(FrontendDelivery::from).collect(),
+    )),
+    Err(e) => {
+      error!("error while trying to query webhook deliveries {e}");
+      Err(StatusCode::INTERNAL_SERVER_ERROR)
+    }
+  }
+}
+
+/// Overrides the disk quota of the repositories of an installation, or resets it to the default.
+pub(super) async fn admin_set_quota(
+  _admin: Admin,
+  State(state): State<DoubleBlindState>,
+  Path(installation_id): Path<i64>,
+  Json(data): Json<QuotaSettings>,
+) -> Result<StatusCode, StatusCode> {
+  let quota = match data.quota.map(i64::try_from).transpose() {
+    Ok(value) => value,
+    Err(_) => {
+      info!("rejecting quota which does not fit into the database");
+      return Err(StatusCode::BAD_REQUEST);
+    }
+  };
+
+  match state
+    .project_service
+    .set_quota(installation_id, quota)
+    .await
+  {
+    Ok(Some(github_app)) => {
+      info!(
+        "Set quota of installation {} to {:?}",
+        github_app.installation_id, github_app.quota
+      );
+      Ok(StatusCode::NO_CONTENT)
+    }
+    Ok(None) => Err(StatusCode::NOT_FOUND),
+    Err(e) => {
+      error!("error while trying to set quota {e}");
+      Err(StatusCode::INTERNAL_SERVER_ERROR)
+    }
+  }
+}
@@ -1,16 +1,17 @@
-use axum::extract::State;
 use axum::http::StatusCode;
-use axum::Json;
 use serde::{Deserialize, Serialize};
 use tracing::{error, info};
 
+use entity::deployment::DeploymentTrigger;
+
+use crate::routes::GithubRepoEdit;
 use crate::service::deploy::DeploymentInformation;
-use crate::service::token::ResponseAccessTokens;
 use crate::state::DoubleBlindState;
 
 #[derive(Serialize, Deserialize)]
 pub(super) struct RepositoryInformationGithub {
   id: i64,
+  name: String,
   full_name: String,
   size: i64,
   default_branch: String,
@@ -21,18 +22,30 @@ pub(super) struct GithubWebhookRequest {
   r#ref: String,
   before: String,
   after: String,
+  /// set when the branch itself was deleted
+  #[serde(default)]
+  deleted: bool,
   repository: RepositoryInformationGithub,
 }
 
-pub(super) async fn github_deploy_webhook(
-  State(mut state): State<DoubleBlindState>,
-  data: Json<GithubWebhookRequest>,
+/// Deploys the pushed commit if it is on the branch the site is published from.
+pub(super) async fn push_event(
+  state: &DoubleBlindState,
+  data: GithubWebhookRequest,
 ) -> Result<StatusCode, StatusCode> {
   info!("New Deployment for {}", &data.repository.full_name);
 
+  // the repository might have been renamed without us noticing
   let repository = match state
     .project_service
-    .get_repository(data.repository.id)
+    .update_repository(
+      GithubRepoEdit {
+        id: data.repository.id,
+        name: data.repository.name.clone(),
+        full_name: data.repository.full_name.clone(),
+      },
+      None,
+    )
     .await
   {
     Ok(Some(value)) => value,
@@ -41,7 +54,8 @@ pub(super) async fn github_deploy_webhook(
         "github tried to call webhook for undeployed repo {}",
         data.repository.full_name
       );
-      return Err(StatusCode::NOT_FOUND);
+      // the github app receives pushes of every repository it is installed on
+      return Ok(StatusCode::NO_CONTENT);
     }
     Err(e) => {
       error!("error while trying to query repo {e}");
@@ -49,12 +63,12 @@ pub(super) async fn github_deploy_webhook(
     }
   };
 
-  if !repository.deployed {
-    return Err(StatusCode::BAD_REQUEST);
+  if !repository.deployed || data.deleted {
+    return Ok(StatusCode::NO_CONTENT);
   }
 
-  let (domain, branch) = match (repository.domain, repository.branch) {
-    (Some(new_domain), Some(new_branch)) => (new_domain, new_branch),
+  let branch = match (repository.domain, repository.branch) {
+    (Some(_), Some(new_branch)) => new_branch,
     _ => {
       error!("No Domain or Branch specified in database!");
       return Err(StatusCode::INTERNAL_SERVER_ERROR);
@@ -65,44 +79,12 @@ pub(super) async fn github_deploy_webhook(
     return Ok(StatusCode::NO_CONTENT);
   }
 
-  let github_app = match state
-    .project_service
-    .get_github_app_uuid(repository.github_app)
-    .await
-  {
-    Ok(Some(value)) => value,
-    Ok(None) => {
-      info!(
-        "no github installation with this name! - github-app-id: {}",
-        &repository.github_app
-      );
-      return Err(StatusCode::NOT_FOUND);
-    }
-    Err(e) => {
-      error!("error while trying to query github apps {e}");
-      return Err(StatusCode::INTERNAL_SERVER_ERROR);
-    }
-  };
-
-  let access_token: ResponseAccessTokens = state
-    .token_service
-    .fetch_access_tokens_repo(
-      github_app.installation_id,
-      vec![repository.github_short_name],
-    )
-    .await
-    .map_err(|e| {
-      error!("error while trying to fetch access token {e}");
-      StatusCode::INTERNAL_SERVER_ERROR
-    })?;
-
   state
     .deployment_service
     .queue_deployment(DeploymentInformation {
-      full_name: repository.github_full_name,
-      token: access_token.token,
-      domain,
+      repository: repository.id,
       commit_id: data.after.clone(),
+      trigger: DeploymentTrigger::Webhook,
     })
     .await
     .map_err(|_e| {
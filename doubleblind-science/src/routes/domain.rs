@@ -0,0 +1,172 @@
+use axum::extract::{Path, State};
+use axum::http::StatusCode;
+use axum::Json;
+use sea_orm::{DbErr, SqlErr};
+use serde::{Deserialize, Serialize};
+use time::OffsetDateTime;
+use tracing::{error, info};
+use uuid::Uuid;
+
+use entity::custom_domain;
+
+use crate::auth::Session;
+use crate::routes::repository::authorized_repository;
+use crate::service::custom_domain::{challenge_name, challenge_value};
+use crate::state::DoubleBlindState;
+
+#[derive(Serialize)]
+pub(super) struct FrontendCustomDomain {
+  id: Uuid,
+  hostname: String,
+  verified: bool,
+  #[serde(with = "time::serde::rfc3339::option")]
+  verified_at: Option<OffsetDateTime>,
+  /// txt record the author has to create before the domain is verified
+  record_name: String,
+  record_value: String,
+  #[serde(with = "time::serde::rfc3339")]
+  created_at: OffsetDateTime,
+}
+
+#[derive(Deserialize)]
+pub(super) struct NewCustomDomain {
+  hostname: String,
+}
+
+impl From<custom_domain::Model> for FrontendCustomDomain {
+  fn from(value: custom_domain::Model) -> Self {
+    FrontendCustomDomain {
+      id: value.id,
+      record_name: challenge_name(&value.hostname),
+      record_value: challenge_value(&value.token),
+      hostname: value.hostname,
+      verified: value.verified_at.is_some(),
+      verified_at: value.verified_at,
+      created_at: value.created_at,
+    }
+  }
+}
+
+fn is_taken(error: &anyhow::Error) -> bool {
+  matches!(
+    error.downcast_ref::<DbErr>().and_then(DbErr::sql_err),
+    Some(SqlErr::UniqueConstraintViolation(_))
+  )
+}
+
+pub(super) async fn github_repo_domains(
+  Session(session): Session,
+  State(state): State<DoubleBlindState>,
+  Path(github_id): Path<i64>,
+) -> Result<Json<Vec<FrontendCustomDomain>>, StatusCode> {
+  let repo = authorized_repository(&state, &session, github_id).await?;
+
+  match state.custom_domain_service.domains(repo.id).await {
+    Ok(domains) => Ok(Json(
+      domains
+        .into_iter()
+        .map(FrontendCustomDomain::from)
+        .collect(),
+    )),
+    Err(e) => {
+      error!("error while trying to query custom domains {e}");
+      Err(StatusCode::INTERNAL_SERVER_ERROR)
+    }
+  }
+}
+
+/// Adds a hostname to the repository, it is served once its txt record has been verified.
+pub(super) async fn github_repo_add_domain(
+  Session(session): Session,
+  State(state): State<DoubleBlindState>,
+  Path(github_id): Path<i64>,
+  Json(data): Json<NewCustomDomain>,
+) -> Result<Json<FrontendCustomDomain>, StatusCode> {
+  let repo = authorized_repository(&state, &session, github_id).await?;
+
+  let hostname = state
+    .custom_domain_service
+    .validate(&data.hostname)
+    .map_err(|e| {
+      info!("rejecting custom domain: {e}");
+      StatusCode::BAD_REQUEST
+    })?;
+
+  match state.custom_domain_service.add(repo.id, hostname).await {
+    Ok(domain) => Ok(Json(domain.into())),
+    Err(e) if is_taken(&e) => {
+      info!("custom domain was already added to this repository");
+      Err(StatusCode::CONFLICT)
+    }
+    Err(e) => {
+      error!("error while trying to add custom domain {e}");
+      Err(StatusCode::INTERNAL_SERVER_ERROR)
+    }
+  }
+}
+
+/// Looks up the txt record of the domain and starts serving the site under it once it matches.
+pub(super) async fn github_repo_verify_domain(
+  Session(session): Session,
+  State(state): State<DoubleBlindState>,
+  Path((github_id, domain_id)): Path<(i64, Uuid)>,
+) -> Result<Json<FrontendCustomDomain>, StatusCode> {
+  let repo = authorized_repository(&state, &session, github_id).await?;
+
+  let domain = match state.custom_domain_service.verify(repo.id, domain_id).await {
+    Ok(Some(value)) => value,
+    Ok(None) => return Err(StatusCode::NOT_FOUND),
+    Err(e) if is_taken(&e) => {
+      info!("custom domain is already verified for another repository");
+      return Err(StatusCode::CONFLICT);
+    }
+    Err(e) => {
+      error!("error while trying to verify custom domain {e}");
+      return Err(StatusCode::BAD_GATEWAY);
+    }
+  };
+
+  if let (true, true, Some(site)) = (domain.verified_at.is_some(), repo.deployed, &repo.domain) {
+    if let Err(e) = state
+      .deployment_service
+      .link_custom_domains(repo.id, site)
+      .await
+    {
+      error!("cannot link custom domain {e}");
+      return Err(StatusCode::INTERNAL_SERVER_ERROR);
+    }
+  }
+
+  Ok(Json(domain.into()))
+}
+
+pub(super) async fn github_repo_delete_domain(
+  Session(session): Session,
+  State(state): State<DoubleBlindState>,
+  Path((github_id, domain_id)): Path<(i64, Uuid)>,
+) -> Result<StatusCode, StatusCode> {
+  let repo = authorized_repository(&state, &session, github_id).await?;
+
+  let domain = match state.custom_domain_service.delete(repo.id, domain_id).await {
+    Ok(Some(value)) => value,
+    Ok(None) => return Err(StatusCode::NOT_FOUND),
+    Err(e) => {
+      error!("error while trying to delete custom domain {e}");
+      return Err(StatusCode::INTERNAL_SERVER_ERROR);
+    }
+  };
+
+  // unverified domains were never linked
+  if domain.verified_at.is_some() {
+    if let Err(e) = state
+      .deployment_service
+      .unlink_custom_domain(&domain.hostname)
+      .await
+    {
+      error!("cannot unlink custom domain {e}");
+      return Err(StatusCode::INTERNAL_SERVER_ERROR);
+    }
+  }
+
+  Ok(StatusCode::NO_CONTENT)
+}
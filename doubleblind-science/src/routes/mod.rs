@@ -1,18 +1,37 @@
-use axum::routing::{get, post};
+use axum::routing::{delete, get, post, put};
 use axum::Router;
 use serde::{Deserialize, Serialize};
 use time::OffsetDateTime;
 
-use crate::routes::deploy::github_deploy_webhook;
+use crate::routes::admin::{admin_deliveries, admin_set_quota};
+use crate::routes::access::{site_access_check, site_access_page, site_access_redeem};
+use crate::routes::domain::{
+  github_repo_add_domain, github_repo_delete_domain, github_repo_domains, github_repo_verify_domain,
+};
+use crate::routes::repository::{
+  github_deployment_redactions, github_repo_access_codes, github_repo_create_access_code,
+  github_repo_delete_access_code, github_repo_deployments, github_repo_redactions,
+  github_repo_set_camera_ready, github_repo_set_fallback, github_repo_set_redactions,
+  github_repo_set_schedule,
+};
 use crate::routes::setup::{
-  github_app_deploy_website, github_app_repositories, github_create_installation,
+  github_app_deploy_website, github_app_repositories, github_app_rollback_website,
   github_forward_user,
 };
+use crate::routes::webhook::github_webhook;
 use crate::state::DoubleBlindState;
 
+mod access;
+mod admin;
 mod deploy;
+mod domain;
 mod setup;
 mod auth;
+mod repository;
+mod serve;
+mod webhook;
+
+pub(crate) use serve::static_route;
 
 #[derive(Serialize, Deserialize, Clone)]
 pub struct GithubRepoInformation {
@@ -35,9 +54,66 @@ pub struct GithubRepoEdit {
 
 pub(crate) fn route() -> Router<DoubleBlindState> {
   Router::new()
-    .route("/v1/github/hooks/deploy", post(github_deploy_webhook))
-    .route("/v1/github/hooks/setup", post(github_create_installation))
+    .route("/v1/github/hooks", post(github_webhook))
+    // older app configurations and repository hooks still deliver here
+    .route("/v1/github/hooks/deploy", post(github_webhook))
+    .route("/v1/github/hooks/setup", post(github_webhook))
     .route("/v1/github/hooks/setup", get(github_forward_user))
     .route("/v1/github/repos", get(github_app_repositories))
+    .route(
+      "/v1/github/repos/:id/deployments",
+      get(github_repo_deployments),
+    )
+    .route(
+      "/v1/github/repos/:id/deployments/:deployment/redactions",
+      get(github_deployment_redactions),
+    )
+    .route(
+      "/v1/github/repos/:id/redactions",
+      get(github_repo_redactions).put(github_repo_set_redactions),
+    )
+    .route(
+      "/v1/github/repos/:id/camera-ready",
+      put(github_repo_set_camera_ready),
+    )
+    .route(
+      "/v1/github/repos/:id/schedule",
+      put(github_repo_set_schedule),
+    )
+    .route(
+      "/v1/github/repos/:id/fallback",
+      put(github_repo_set_fallback),
+    )
+    .route(
+      "/v1/github/repos/:id/access-codes",
+      get(github_repo_access_codes).post(github_repo_create_access_code),
+    )
+    .route(
+      "/v1/github/repos/:id/access-codes/:code",
+      delete(github_repo_delete_access_code),
+    )
+    .route(
+      "/v1/github/repos/:id/domains",
+      get(github_repo_domains).post(github_repo_add_domain),
+    )
+    .route(
+      "/v1/github/repos/:id/domains/:domain",
+      delete(github_repo_delete_domain),
+    )
+    .route(
+      "/v1/github/repos/:id/domains/:domain/verify",
+      post(github_repo_verify_domain),
+    )
+    .route(
+      "/.doubleblind/access",
+      get(site_access_page).post(site_access_redeem),
+    )
+    .route("/.doubleblind/access/check", get(site_access_check))
     .route("/v1/github/deploy", post(github_app_deploy_website))
+    .route("/v1/github/rollback", post(github_app_rollback_website))
+    .route("/v1/admin/deliveries", get(admin_deliveries))
+    .route(
+      "/v1/admin/installations/:installation_id/quota",
+      put(admin_set_quota),
+    )
 }
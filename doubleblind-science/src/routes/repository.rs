@@ -0,0 +1,441 @@
+use axum::extract::{Path, State};
+use axum::http::StatusCode;
+use axum::Json;
+use serde::{Deserialize, Serialize};
+use time::OffsetDateTime;
+use tracing::{error, info};
+use uuid::Uuid;
+
+use entity::deployment::{DeploymentStatus, DeploymentTrigger};
+use entity::repository;
+use entity::repository::{CameraReadyLink, ExpiryAction, SiteFallback};
+
+use crate::auth::{Session, SessionData};
+use crate::service::access::validate_label;
+use crate::service::anonymize::validate_terms;
+use crate::service::deploy::DeploymentInformation;
+use crate::state::DoubleBlindState;
+
+#[derive(Serialize)]
+pub(super) struct FrontendDeploymentInformation {
+  id: Uuid,
+  commit_id: String,
+  status: DeploymentStatus,
+  trigger: DeploymentTrigger,
+  error: Option<String>,
+  anonymized: Option<bool>,
+  #[serde(with = "time::serde::rfc3339")]
+  created_at: OffsetDateTime,
+  #[serde(with = "time::serde::rfc3339::option")]
+  started_at: Option<OffsetDateTime>,
+  #[serde(with = "time::serde::rfc3339::option")]
+  finished_at: Option<OffsetDateTime>,
+  duration_ms: Option<i64>,
+}
+
+#[derive(Serialize)]
+pub(super) struct FrontendRedactionReport {
+  path: String,
+  replacements: i32,
+}
+
+#[derive(Serialize)]
+pub(super) struct FrontendAccessCode {
+  id: Uuid,
+  label: String,
+  /// only present right after the code was created
+  #[serde(skip_serializing_if = "Option::is_none")]
+  code: Option<String>,
+  #[serde(with = "time::serde::rfc3339")]
+  created_at: OffsetDateTime,
+}
+
+#[derive(Deserialize)]
+pub(super) struct SiteSchedule {
+  /// the site stays hidden until then
+  #[serde(default, with = "time::serde::rfc3339::option")]
+  publish_after: Option<OffsetDateTime>,
+  /// the expiry action is applied at this point
+  #[serde(default, with = "time::serde::rfc3339::option")]
+  expires_at: Option<OffsetDateTime>,
+  #[serde(default = "default_expiry_action")]
+  expiry_action: ExpiryAction,
+}
+
+fn default_expiry_action() -> ExpiryAction {
+  ExpiryAction::Unpublish
+}
+
+#[derive(Deserialize)]
+pub(super) struct FallbackSettings {
+  fallback: SiteFallback,
+}
+
+#[derive(Deserialize)]
+pub(super) struct NewAccessCode {
+  label: String,
+}
+
+#[derive(Deserialize)]
+pub(super) struct CameraReadySettings {
+  enabled: bool,
+  /// banner or redirect pointing visitors to the real repository
+  #[serde(default)]
+  link: Option<CameraReadyLink>,
+}
+
+/// Looks up the repository by its github id and makes sure it belongs to the session.
+pub(super) async fn authorized_repository(
+  state: &DoubleBlindState,
+  session: &SessionData,
+  github_id: i64,
+) -> Result<repository::Model, StatusCode> {
+  let repo = match state.project_service.get_repository(github_id).await {
+    Ok(Some(value)) => value,
+    Ok(None) => return Err(StatusCode::NOT_FOUND),
+    Err(e) => {
+      error!("error while trying to query repo {e}");
+      return Err(StatusCode::INTERNAL_SERVER_ERROR);
+    }
+  };
+
+  let github_app = match state
+    .project_service
+    .get_github_app_uuid(repo.github_app)
+    .await
+  {
+    Ok(Some(value)) => value,
+    Ok(None) => return Err(StatusCode::NOT_FOUND),
+    Err(e) => {
+      error!("error while trying to query github apps {e}");
+      return Err(StatusCode::INTERNAL_SERVER_ERROR);
+    }
+  };
+
+  if !session
+    .installation_id
+    .contains(&github_app.installation_id)
+  {
+    return Err(StatusCode::FORBIDDEN);
+  }
+
+  Ok(repo)
+}
+
+pub(super) async fn github_repo_deployments(
+  Session(session): Session,
+  State(state): State<DoubleBlindState>,
+  Path(github_id): Path<i64>,
+) -> Result<Json<Vec<FrontendDeploymentInformation>>, StatusCode> {
+  let repo = authorized_repository(&state, &session, github_id).await?;
+
+  let deployments = state
+    .deployment_service
+    .deployments(repo.id)
+    .await
+    .map_err(|e| {
+      error!("error while trying to query deployments {e}");
+      StatusCode::INTERNAL_SERVER_ERROR
+    })?;
+
+  Ok(Json(
+    deployments
+      .into_iter()
+      .map(|x| FrontendDeploymentInformation {
+        id: x.id,
+        commit_id: x.commit_id,
+        status: x.status,
+        trigger: x.trigger,
+        error: x.error,
+        anonymized: x.anonymized,
+        created_at: x.created_at,
+        started_at: x.started_at,
+        finished_at: x.finished_at,
+        duration_ms: match (x.started_at, x.finished_at) {
+          (Some(started_at), Some(finished_at)) => {
+            Some((finished_at - started_at).whole_milliseconds() as i64)
+          }
+          _ => None,
+        },
+      })
+      .collect(),
+  ))
+}
+
+pub(super) async fn github_repo_redactions(
+  Session(session): Session,
+  State(state): State<DoubleBlindState>,
+  Path(github_id): Path<i64>,
+) -> Result<Json<Vec<String>>, StatusCode> {
+  let repo = authorized_repository(&state, &session, github_id).await?;
+
+  state
+    .project_service
+    .redaction_terms(repo.id)
+    .await
+    .map(Json)
+    .map_err(|e| {
+      error!("error while trying to query redaction terms {e}");
+      StatusCode::INTERNAL_SERVER_ERROR
+    })
+}
+
+/// Replaces the identity strings which are removed from every future deployment of the repository.
+pub(super) async fn github_repo_set_redactions(
+  Session(session): Session,
+  State(state): State<DoubleBlindState>,
+  Path(github_id): Path<i64>,
+  Json(terms): Json<Vec<String>>,
+) -> Result<Json<Vec<String>>, StatusCode> {
+  let repo = authorized_repository(&state, &session, github_id).await?;
+
+  let terms = validate_terms(terms).map_err(|e| {
+    info!("rejecting redaction terms: {e}");
+    StatusCode::BAD_REQUEST
+  })?;
+
+  state
+    .project_service
+    .set_redaction_terms(repo.id, terms.clone())
+    .await
+    .map_err(|e| {
+      error!("error while trying to store redaction terms {e}");
+      StatusCode::INTERNAL_SERVER_ERROR
+    })?;
+
+  Ok(Json(terms))
+}
+
+pub(super) async fn github_deployment_redactions(
+  Session(session): Session,
+  State(state): State<DoubleBlindState>,
+  Path((github_id, deployment_id)): Path<(i64, Uuid)>,
+) -> Result<Json<Vec<FrontendRedactionReport>>, StatusCode> {
+  let repo = authorized_repository(&state, &session, github_id).await?;
+
+  match state
+    .deployment_service
+    .redaction_report(repo.id, deployment_id)
+    .await
+  {
+    Ok(Some(value)) => Ok(Json(
+      value
+        .into_iter()
+        .map(|x| FrontendRedactionReport {
+          path: x.path,
+          replacements: x.replacements,
+        })
+        .collect(),
+    )),
+    Ok(None) => Err(StatusCode::NOT_FOUND),
+    Err(e) => {
+      error!("error while trying to query redaction report {e}");
+      Err(StatusCode::INTERNAL_SERVER_ERROR)
+    }
+  }
+}
+
+/// Switches the repository between its anonymized and original content and republishes the commit
+/// which is currently live.
+pub(super) async fn github_repo_set_camera_ready(
+  Session(session): Session,
+  State(state): State<DoubleBlindState>,
+  Path(github_id): Path<i64>,
+  Json(data): Json<CameraReadySettings>,
+) -> Result<StatusCode, StatusCode> {
+  let repo = authorized_repository(&state, &session, github_id).await?;
+
+  let repo = state
+    .project_service
+    .set_camera_ready(repo.id, data.enabled, data.link)
+    .await
+    .map_err(|e| {
+      error!("error while trying to store camera ready setting {e}");
+      StatusCode::INTERNAL_SERVER_ERROR
+    })?;
+
+  if !repo.deployed {
+    return Ok(StatusCode::OK);
+  }
+
+  let commit_id = match state.deployment_service.current_commit(repo.id).await {
+    Ok(Some(value)) => value,
+    Ok(None) => return Ok(StatusCode::OK),
+    Err(e) => {
+      error!("error while trying to query deployments {e}");
+      return Err(StatusCode::INTERNAL_SERVER_ERROR);
+    }
+  };
+
+  info!(
+    "Republishing {}#{} with camera ready {}",
+    &repo.github_full_name, &commit_id, data.enabled
+  );
+
+  state
+    .deployment_service
+    .queue_deployment(DeploymentInformation {
+      repository: repo.id,
+      commit_id,
+      trigger: DeploymentTrigger::CameraReady,
+    })
+    .await
+    .map_err(|e| {
+      error!("queueing for deployment failed {e}");
+      StatusCode::INTERNAL_SERVER_ERROR
+    })?;
+
+  Ok(StatusCode::ACCEPTED)
+}
+
+pub(super) async fn github_repo_access_codes(
+  Session(session): Session,
+  State(state): State<DoubleBlindState>,
+  Path(github_id): Path<i64>,
+) -> Result<Json<Vec<FrontendAccessCode>>, StatusCode> {
+  let repo = authorized_repository(&state, &session, github_id).await?;
+
+  let codes = state.access_service.codes(repo.id).await.map_err(|e| {
+    error!("error while trying to query access codes {e}");
+    StatusCode::INTERNAL_SERVER_ERROR
+  })?;
+
+  Ok(Json(
+    codes
+      .into_iter()
+      .map(|x| FrontendAccessCode {
+        id: x.id,
+        label: x.label,
+        code: None,
+        created_at: x.created_at,
+      })
+      .collect(),
+  ))
+}
+
+/// Creates a code reviewers have to enter before they can see the site, the site is public as long
+/// as it has none. Codes are only available if a secret for signing the cookies is configured.
+pub(super) async fn github_repo_create_access_code(
+  Session(session): Session,
+  State(state): State<DoubleBlindState>,
+  Path(github_id): Path<i64>,
+  Json(data): Json<NewAccessCode>,
+) -> Result<Json<FrontendAccessCode>, StatusCode> {
+  let repo = authorized_repository(&state, &session, github_id).await?;
+
+  if !state.access_service.codes_enabled() {
+    info!("rejecting access code: no access secret is configured");
+    return Err(StatusCode::NOT_FOUND);
+  }
+
+  let label = validate_label(&data.label).map_err(|e| {
+    info!("rejecting access code: {e}");
+    StatusCode::BAD_REQUEST
+  })?;
+
+  let (model, code) = state
+    .access_service
+    .create_code(repo.id, label)
+    .await
+    .map_err(|e| {
+      error!("error while trying to create access code {e}");
+      StatusCode::INTERNAL_SERVER_ERROR
+    })?;
+
+  Ok(Json(FrontendAccessCode {
+    id: model.id,
+    label: model.label,
+    code: Some(code),
+    created_at: model.created_at,
+  }))
+}
+
+pub(super) async fn github_repo_delete_access_code(
+  Session(session): Session,
+  State(state): State<DoubleBlindState>,
+  Path((github_id, code_id)): Path<(i64, Uuid)>,
+) -> Result<StatusCode, StatusCode> {
+  let repo = authorized_repository(&state, &session, github_id).await?;
+
+  match state.access_service.delete_code(repo.id, code_id).await {
+    Ok(true) => Ok(StatusCode::NO_CONTENT),
+    Ok(false) => Err(StatusCode::NOT_FOUND),
+    Err(e) => {
+      error!("error while trying to delete access code {e}");
+      Err(StatusCode::INTERNAL_SERVER_ERROR)
+    }
+  }
+}
+
+/// Sets the review period of the site, after which it is taken down or made public.
+pub(super) async fn github_repo_set_schedule(
+  Session(session): Session,
+  State(state): State<DoubleBlindState>,
+  Path(github_id): Path<i64>,
+  Json(data): Json<SiteSchedule>,
+) -> Result<StatusCode, StatusCode> {
+  let repo = authorized_repository(&state, &session, github_id).await?;
+
+  if let (Some(publish_after), Some(expires_at)) = (data.publish_after, data.expires_at) {
+    if publish_after >= expires_at {
+      info!("rejecting schedule which expires before it is published");
+      return Err(StatusCode::BAD_REQUEST);
+    }
+  }
+
+  if data
+    .expires_at
+    .is_some_and(|expires_at| expires_at <= OffsetDateTime::now_utc())
+  {
+    info!("rejecting schedule which expired already");
+    return Err(StatusCode::BAD_REQUEST);
+  }
+
+  let repo = state
+    .project_service
+    .set_schedule(
+      repo.id,
+      data.publish_after,
+      data.expires_at,
+      data.expiry_action,
+    )
+    .await
+    .map_err(|e| {
+      error!("error while trying to store schedule {e}");
+      StatusCode::INTERNAL_SERVER_ERROR
+    })?;
+
+  // the site goes offline until the new publish date, or online if it was removed
+  state
+    .deployment_service
+    .apply_schedule(&repo)
+    .await
+    .map_err(|e| {
+      error!("error while trying to apply schedule {e}");
+      StatusCode::INTERNAL_SERVER_ERROR
+    })?;
+
+  Ok(StatusCode::OK)
+}
+
+/// Chooses between the custom 404 page and the index page of single page applications for paths
+/// without a file.
+pub(super) async fn github_repo_set_fallback(
+  Session(session): Session,
+  State(state): State<DoubleBlindState>,
+  Path(github_id): Path<i64>,
+  Json(data): Json<FallbackSettings>,
+) -> Result<StatusCode, StatusCode> {
+  let repo = authorized_repository(&state, &session, github_id).await?;
+
+  state
+    .project_service
+    .set_fallback(repo.id, data.fallback)
+    .await
+    .map_err(|e| {
+      error!("error while trying to store fallback {e}");
+      StatusCode::INTERNAL_SERVER_ERROR
+    })?;
+
+  Ok(StatusCode::OK)
+}
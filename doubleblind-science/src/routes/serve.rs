@@ -0,0 +1,250 @@
+use std::path::Path;
+
+use axum::body::{boxed, Body, BoxBody};
+use axum::extract::State;
+use axum::http::{header, HeaderValue, Request, StatusCode, Uri};
+use axum::response::{IntoResponse, Response};
+use axum::routing::get;
+use axum::Router;
+use axum_extra::extract::CookieJar;
+use percent_encoding::percent_decode_str;
+use sha2::{Digest, Sha256};
+use tower_http::services::{ServeDir, ServeFile};
+use tracing::error;
+
+use entity::repository::SiteFallback;
+
+use crate::routes::access::{site_access_page, site_access_redeem, site_domain};
+use crate::service::access::{SiteAccess, ACCESS_COOKIE};
+use crate::service::redirects::RedirectRules;
+use crate::state::DoubleBlindState;
+
+/// Serves the deployed sites by their `Host` header, for setups without a separate web server.
+pub(crate) fn static_route() -> Router<DoubleBlindState> {
+  Router::new()
+    .route(
+      "/.doubleblind/access",
+      get(site_access_page).post(site_access_redeem),
+    )
+    .fallback(serve_site)
+}
+
+async fn serve_site(
+  State(state): State<DoubleBlindState>,
+  jar: CookieJar,
+  request: Request<Body>,
+) -> Response {
+  let domain = match site_domain(&state, request.headers()).await {
+    Ok(Some(value)) => value,
+    Ok(None) => return StatusCode::NOT_FOUND.into_response(),
+    Err(e) => {
+      error!("error while looking up site {e}");
+      return StatusCode::INTERNAL_SERVER_ERROR.into_response();
+    }
+  };
+
+  let fallback = match state
+    .access_service
+    .authorize(&domain, jar.get(ACCESS_COOKIE).map(|cookie| cookie.value()))
+    .await
+  {
+    Ok(SiteAccess::Granted(fallback)) => fallback,
+    Ok(SiteAccess::CodeRequired) => return site_access_page().await.into_response(),
+    Ok(SiteAccess::Hidden) => return StatusCode::NOT_FOUND.into_response(),
+    Err(e) => {
+      error!("error while checking access to {domain} {e}");
+      return StatusCode::INTERNAL_SERVER_ERROR.into_response();
+    }
+  };
+
+  let site = state.deployment_service.site_directory(&domain);
+  let if_none_match = request.headers().get(header::IF_NONE_MATCH).cloned();
+
+  // the rules were checked during the deployment, a broken file only disables them
+  let redirects = RedirectRules::load(&site).await.unwrap_or_else(|e| {
+    error!("cannot load redirects of {domain} {e}");
+    RedirectRules::default()
+  });
+
+  let mut request = request;
+  let mut status = None;
+  if let Some(redirect) = redirects.find(request.uri().path()) {
+    if redirect.force || !has_file(&site, request.uri().path()).await {
+      let target = with_query(&redirect.to, request.uri());
+
+      if redirect.status != 200 && redirect.status != 404 {
+        return match (
+          StatusCode::from_u16(redirect.status),
+          HeaderValue::from_str(&target),
+        ) {
+          (Ok(status), Ok(location)) => (status, [(header::LOCATION, location)]).into_response(),
+          _ => {
+            error!("invalid redirect of {domain} to {target}");
+            StatusCode::INTERNAL_SERVER_ERROR.into_response()
+          }
+        };
+      }
+
+      *request.uri_mut() = match Uri::try_from(target) {
+        Ok(uri) => uri,
+        Err(e) => {
+          error!("invalid rewrite of {domain} {e}");
+          return StatusCode::INTERNAL_SERVER_ERROR.into_response();
+        }
+      };
+      if redirect.status == 404 {
+        status = Some(StatusCode::NOT_FOUND);
+      }
+    }
+  }
+
+  let serve_dir = ServeDir::new(&site)
+    .append_index_html_on_directories(true)
+    .precompressed_br()
+    .precompressed_gzip();
+
+  let response = match fallback {
+    SiteFallback::NotFound => serve_dir
+      .not_found_service(ServeFile::new(site.join("404.html")))
+      .try_call(request)
+      .await
+      .map(|response| response.map(boxed)),
+    // single page applications route on the client, so every unknown path gets the index page
+    SiteFallback::Spa => serve_dir
+      .fallback(ServeFile::new(site.join("index.html")))
+      .try_call(request)
+      .await
+      .map(|response| response.map(boxed)),
+  };
+
+  match response {
+    Ok(mut response) => {
+      if let Some(status) = status {
+        *response.status_mut() = status;
+      }
+      with_etag(response, if_none_match.as_ref())
+    }
+    Err(e) => {
+      error!("cannot serve file of {domain} {e}");
+      StatusCode::INTERNAL_SERVER_ERROR.into_response()
+    }
+  }
+}
+
+/// Whether a file would be served for the path, in which case rules without `!` do not apply.
+async fn has_file(site: &Path, path: &str) -> bool {
+  let path = percent_decode_str(path).decode_utf8_lossy();
+  let mut file = site.to_path_buf();
+
+  for segment in path.split('/').filter(|segment| !segment.is_empty()) {
+    if segment == ".." || segment.contains('\\') {
+      return false;
+    }
+    file.push(segment);
+  }
+
+  match tokio::fs::metadata(&file).await {
+    Ok(metadata) if metadata.is_dir() => tokio::fs::metadata(file.join("index.html"))
+      .await
+      .is_ok_and(|metadata| metadata.is_file()),
+    Ok(metadata) => metadata.is_file(),
+    Err(_) => false,
+  }
+}
+
+/// Passes the query of the request on unless the target has its own.
+fn with_query(target: &str, uri: &Uri) -> String {
+  match uri.query() {
+    Some(query) if !target.contains('?') => format!("{target}?{query}"),
+    _ => target.to_string(),
+  }
+}
+
+/// Adds a weak etag derived from size, modification time and encoding of the file, which tower-http
+/// does not send, and answers matching conditional requests with 304.
+fn with_etag(mut response: Response<BoxBody>, if_none_match: Option<&HeaderValue>) -> Response {
+  if response.status() != StatusCode::OK {
+    return response;
+  }
+
+  let headers = response.headers();
+  let (length, modified) = match (
+    headers.get(header::CONTENT_LENGTH),
+    headers.get(header::LAST_MODIFIED),
+  ) {
+    (Some(length), Some(modified)) => (length, modified),
+    _ => return response,
+  };
+
+  let mut hash = Sha256::new();
+  hash.update(length.as_bytes());
+  hash.update(b"\n");
+  hash.update(modified.as_bytes());
+  if let Some(encoding) = headers.get(header::CONTENT_ENCODING) {
+    hash.update(b"\n");
+    hash.update(encoding.as_bytes());
+  }
+  let etag = format!("W/\"{}\"", &hex::encode(hash.finalize())[..16]);
+
+  let matches = if_none_match
+    .and_then(|value| value.to_str().ok())
+    .map(|value| {
+      value
+        .split(',')
+        .map(str::trim)
+        .any(|candidate| candidate == "*" || candidate == etag)
+    })
+    .unwrap_or(false);
+
+  let etag = HeaderValue::from_str(&etag).expect("etag is a valid header value");
+
+  if matches {
+    let mut not_modified = StatusCode::NOT_MODIFIED.into_response();
+    not_modified
+      .headers_mut()
+      .insert(header::ETAG, etag.clone());
+    if let Some(modified) = response.headers().get(header::LAST_MODIFIED) {
+      not_modified
+        .headers_mut()
+        .insert(header::LAST_MODIFIED, modified.clone());
+    }
+    return not_modified;
+  }
+
+  response.headers_mut().insert(header::ETAG, etag);
+  response
+}
+
+#[cfg(test)]
+mod tests {
+  use axum::body::{boxed, Body};
+  use axum::http::{header, HeaderValue, Response, StatusCode};
+
+  use crate::routes::serve::with_etag;
+
+  #[test]
+  fn test_answers_matching_etag() {
+    let file = || {
+      Response::builder()
+        .header(header::CONTENT_LENGTH, "42")
+        .header(header::LAST_MODIFIED, "Wed, 21 Oct 2015 07:28:00 GMT")
+        .body(boxed(Body::from("x")))
+        .unwrap()
+    };
+
+    let response = with_etag(file(), None);
+    assert_eq!(response.status(), StatusCode::OK);
+    let etag = response.headers().get(header::ETAG).unwrap().clone();
+    assert!(etag.to_str().unwrap().starts_with("W/\""));
+
+    let cached = with_etag(
+      file(),
+      Some(&HeaderValue::from_str(&format!("\"other\", {}", etag.to_str().unwrap())).unwrap()),
+    );
+    assert_eq!(cached.status(), StatusCode::NOT_MODIFIED);
+    assert_eq!(cached.headers().get(header::ETAG), Some(&etag));
+
+    let changed = with_etag(file(), Some(&HeaderValue::from_static("W/\"other\"")));
+    assert_eq!(changed.status(), StatusCode::OK);
+  }
+}
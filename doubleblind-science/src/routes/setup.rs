@@ -10,37 +10,24 @@ use axum_extra::extract::{
   cookie::{Cookie, SameSite},
   CookieJar,
 };
-use bytes::Bytes;
-use hmac::{Hmac, Mac};
 use reqwest::Client;
+use sea_orm::{DbErr, SqlErr};
 use serde::{Deserialize, Serialize};
-use sha2::Sha256;
-use time::Duration;
+use time::{Duration, OffsetDateTime};
 use tracing::{error, info};
 use uuid::Uuid;
 
+use entity::deployment::DeploymentTrigger;
+use entity::repository::{CameraReadyLink, ExpiryAction, SiteFallback};
+
 use crate::auth::{Session, SessionData, SESSION_COOKIE};
-use crate::routes::GithubRepoEdit;
-use crate::service::deploy::DeploymentInformation;
+use crate::routes::repository::authorized_repository;
+use crate::service::deploy::{DeploymentInformation, Rollback};
+use crate::service::domain::validate_domain;
+use crate::service::extract::subdirectory;
 use crate::service::token::ResponseAccessTokens;
 use crate::state::DoubleBlindState;
 
-#[derive(Serialize)]
-pub(super) struct WebHookInformation {
-  url: String,
-  content_type: String,
-  insecure_ssl: String,
-  //token: String,
-}
-
-#[derive(Serialize)]
-pub(super) struct WebhookRegistrationRequest {
-  name: String,
-  active: bool,
-  events: Vec<String>,
-  config: WebHookInformation,
-}
-
 #[derive(Deserialize, Debug)]
 pub(super) struct GithubAppRegistrationCallback {
   installation_id: i64,
@@ -50,21 +37,20 @@ pub(super) struct GithubAppRegistrationCallback {
 
 #[derive(Deserialize)]
 pub(super) struct DeploySite {
-  domain: String,
+  /// subdomain of the site, a random one is generated if it is missing
+  #[serde(default)]
+  domain: Option<String>,
   branch: String,
   github_id: i64,
+  /// folder inside of the repository which becomes the root of the site
+  #[serde(default)]
+  path: Option<String>,
 }
 
 #[derive(Deserialize)]
-pub(super) struct InstallationInformation {
-  id: i64,
-}
-
-#[derive(Deserialize)]
-pub(super) struct GithubWebhookSetup {
-  installation: InstallationInformation,
-  repositories_added: Vec<GithubRepoEdit>,
-  repositories_removed: Vec<GithubRepoEdit>,
+pub(super) struct RollbackSite {
+  github_id: i64,
+  commit_id: String,
 }
 
 #[derive(Serialize)]
@@ -76,6 +62,17 @@ pub(super) struct FrontendRepoInformation {
   pub deployed: bool,
   pub domain: Option<String>,
   pub branch: Option<String>,
+  pub path: Option<String>,
+  pub disk_usage: i64,
+  pub quota: u64,
+  pub camera_ready: bool,
+  pub camera_ready_link: Option<CameraReadyLink>,
+  #[serde(with = "time::serde::rfc3339::option")]
+  pub publish_after: Option<OffsetDateTime>,
+  #[serde(with = "time::serde::rfc3339::option")]
+  pub expires_at: Option<OffsetDateTime>,
+  pub expiry_action: ExpiryAction,
+  pub fallback: SiteFallback,
 }
 
 #[derive(Deserialize)]
@@ -122,105 +119,6 @@ pub(super) async fn github_forward_user(
   Ok((jar.add(session_cookie), Redirect::to(SUCCESS_REDIRECT)))
 }
 
-pub(super) async fn github_create_installation(
-  State(mut state): State<DoubleBlindState>,
-  headers: HeaderMap,
-  raw_body: Bytes,
-) -> Result<StatusCode, StatusCode> {
-  info!("setup new github project");
-
-  type HmacSha256 = Hmac<Sha256>;
-
-  let hash = match headers.get("X-Hub-Signature-256") {
-    Some(value) => value,
-    None => {
-      error!("github didn't send HMAC challange hash!");
-      return Err(StatusCode::BAD_REQUEST);
-    }
-  };
-  let hex_string = hex::decode(
-    hash
-      .to_str()
-      .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?
-      .split_at(7)
-      .1,
-  )
-  .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
-
-  match HmacSha256::new_from_slice(state.github_hmac_secret.as_ref()) {
-    Ok(mut mac) => {
-      mac.update(&raw_body);
-      info!("body {}", String::from_utf8_lossy(&raw_body));
-
-      match mac.verify_slice(&hex_string[..]) {
-        Ok(_) => {}
-        Err(e) => {
-          error!("non github entity tried to call the webhook endpoint! {e}");
-          return Err(StatusCode::FORBIDDEN);
-        }
-      }
-    }
-    Err(e) => {
-      error!("cannot generate hmac with error {}", e);
-      return Err(StatusCode::INTERNAL_SERVER_ERROR);
-    }
-  }
-
-  // parsing json body from github
-  let parsed_request: GithubWebhookSetup =
-    serde_json::from_str(&String::from_utf8_lossy(&raw_body)).map_err(|e| {
-      error!(
-        "cannot parse request body from github {} {:?}",
-        &*String::from_utf8_lossy(&raw_body),
-        e
-      );
-      StatusCode::BAD_REQUEST
-    })?;
-
-  state
-    .repos_per_installation
-    .write()
-    .await
-    .push(parsed_request.installation.id);
-
-  // create if github_app doesn't exist yet
-  let github_app_db = match state
-    .project_service
-    .create_github_app(parsed_request.installation.id)
-    .await
-  {
-    Ok(value) => value,
-    Err(e) => {
-      error!("error when trying to create github app {e}");
-
-      return Err(StatusCode::INTERNAL_SERVER_ERROR);
-    }
-  };
-
-  // rewrite the list of repositories connected to github app
-  state
-    .project_service
-    .rewrite_list_of_repositories(
-      github_app_db.id,
-      parsed_request.repositories_added,
-      parsed_request.repositories_removed,
-    )
-    .await
-    .map_err(|e| {
-      error!("error while trying to rewrite repo list {e}");
-
-      StatusCode::INTERNAL_SERVER_ERROR
-    })?;
-
-  state
-    .repos_per_installation
-    .write()
-    .await
-    .retain(|&item| item != parsed_request.installation.id);
-
-  Ok(StatusCode::OK)
-}
-
 pub async fn github_app_repositories(
   Session(session): Session,
   State(state): State<DoubleBlindState>,
@@ -240,7 +138,7 @@ pub async fn github_app_repositories(
     .all_repos_for_installation_id(session.installation_id)
     .await
   {
-    Ok(Some(value)) => Ok(Json(
+    Ok(Some((github_app, value))) => Ok(Json(
       value
         .iter()
         .map(|x| FrontendRepoInformation {
@@ -250,6 +148,15 @@ pub async fn github_app_repositories(
           deployed: x.deployed,
           branch: x.branch.clone(),
           domain: x.domain.clone(),
+          path: x.path.clone(),
+          disk_usage: x.disk_usage,
+          quota: state.deployment_service.quota(&github_app),
+          camera_ready: x.camera_ready,
+          camera_ready_link: x.camera_ready_link.clone(),
+          publish_after: x.publish_after,
+          expires_at: x.expires_at,
+          expiry_action: x.expiry_action.clone(),
+          fallback: x.fallback.clone(),
         })
         .collect::<Vec<FrontendRepoInformation>>(),
     )),
@@ -269,34 +176,57 @@ pub async fn github_app_repositories(
 
 pub async fn github_app_deploy_website(
   Session(session): Session,
-  State(mut state): State<DoubleBlindState>,
+  State(state): State<DoubleBlindState>,
   _jar: CookieJar,
   Json(data): Json<DeploySite>,
 ) -> Result<StatusCode, StatusCode> {
+  let current = authorized_repository(&state, &session, data.github_id).await?;
+
   let github_app = match state
     .project_service
-    .get_github_app(session.installation_id)
+    .get_github_app_uuid(current.github_app)
     .await
   {
     Ok(Some(value)) => value,
-    Ok(None) => {
-      info!(
-        "no github installation with this name! installation_id: {}",
-        &session.installation_id
-      );
-      return Err(StatusCode::NOT_FOUND);
-    }
+    Ok(None) => return Err(StatusCode::NOT_FOUND),
     Err(e) => {
       error!("error while trying to query github apps {e}");
       return Err(StatusCode::INTERNAL_SERVER_ERROR);
     }
   };
 
+  let path = match data.path.as_deref().map(subdirectory).transpose() {
+    Ok(value) => value
+      .flatten()
+      .map(|path| path.to_string_lossy().into_owned()),
+    Err(e) => {
+      info!("rejecting deployment path: {e}");
+      return Err(StatusCode::BAD_REQUEST);
+    }
+  };
+
+  let domain = match data.domain.as_deref().map(validate_domain).transpose() {
+    Ok(value) => value,
+    Err(e) => {
+      info!("rejecting domain: {e}");
+      return Err(StatusCode::BAD_REQUEST);
+    }
+  };
+
   let repo = match state
     .project_service
-    .deploy_repo(data.github_id, data.domain.clone(), data.branch.clone())
+    .deploy_repo(data.github_id, domain, data.branch.clone(), path)
     .await
     .map_err(|e| {
+      let taken = matches!(
+        e.downcast_ref::<DbErr>().and_then(DbErr::sql_err),
+        Some(SqlErr::UniqueConstraintViolation(_))
+      );
+      if taken {
+        info!("domain is already used by another repository");
+        return StatusCode::CONFLICT;
+      }
+
       error!("cannot create repository {e}");
       StatusCode::INTERNAL_SERVER_ERROR
     })?
@@ -308,6 +238,22 @@ pub async fn github_app_deploy_website(
     Some(value) => value.clone(),
   };
 
+  // the old domain would keep serving the site and its releases would never be pruned
+  if let (true, Some(old_domain), Some(new_domain)) =
+    (current.deployed, &current.domain, &repo.domain)
+  {
+    if old_domain != new_domain {
+      if let Err(e) = state
+        .deployment_service
+        .move_site(repo.id, old_domain, new_domain)
+        .await
+      {
+        error!("cannot move site to its new domain {e}");
+        return Err(StatusCode::INTERNAL_SERVER_ERROR);
+      }
+    }
+  }
+
   let access_token: ResponseAccessTokens = state
     .token_service
     .fetch_access_tokens_repo(github_app.installation_id, vec![repo.github_short_name])
@@ -317,38 +263,8 @@ pub async fn github_app_deploy_website(
       StatusCode::INTERNAL_SERVER_ERROR
     })?;
 
+  // pushes arrive through the webhook of the github app, no hook per repository is needed
   let client = Client::new();
-  client
-    .post(format!(
-      "https://api.github.com/repos/{}/hooks",
-      &repo.github_full_name
-    ))
-    .header(reqwest::header::ACCEPT, "application/vnd.github+json")
-    .bearer_auth(&access_token.token)
-    .header("X-GitHub-Api-Version", "2022-11-28")
-    .header(reqwest::header::USER_AGENT, "doubleblind-science")
-    .json(&WebhookRegistrationRequest {
-      name: "web".to_string(),
-      active: true,
-      events: vec!["push".to_string()],
-      config: WebHookInformation {
-        url: "https://api.science.tanneberger.me/v1/github/hooks/deploy".to_string(),
-        content_type: "json".to_string(),
-        insecure_ssl: "0".to_string(),
-      },
-    })
-    .send()
-    .await
-    .map(|response| {
-      info!("Response for Hook Creation {:#?}", response);
-      response
-    })
-    .map_err(|e| {
-      error!("cannot dispatch webhook event with github {e}");
-      StatusCode::INTERNAL_SERVER_ERROR
-    })?
-    .status();
-
   let git_refs: Vec<GithubCommit> = client
     .get(format!(
       "https://api.github.com/repos/{}/git/refs",
@@ -360,12 +276,8 @@ pub async fn github_app_deploy_website(
     .header(reqwest::header::USER_AGENT, "doubleblind-science")
     .send()
     .await
-    .map(|response| {
-      info!("Response for Hook Creation {:#?}", response);
-      response
-    })
     .map_err(|e| {
-      error!("cannot dispatch webhook event with github {e}");
+      error!("cannot fetch git refs of {} from github {e}", &repo.github_full_name);
       StatusCode::INTERNAL_SERVER_ERROR
     })?
     .json::<Vec<GithubCommit>>()
@@ -389,10 +301,9 @@ pub async fn github_app_deploy_website(
   state
     .deployment_service
     .queue_deployment(DeploymentInformation {
-      full_name: repo.github_full_name,
-      token: access_token.token,
-      domain: data.domain,
+      repository: repo.id,
       commit_id: commit_ref.object.sha.clone(),
+      trigger: DeploymentTrigger::Manual,
     })
     .await
     .map_err(|_e| {
@@ -403,3 +314,42 @@ pub async fn github_app_deploy_website(
   Ok(StatusCode::OK)
   // triggering deployment via github webhook
 }
+
+pub async fn github_app_rollback_website(
+  Session(session): Session,
+  State(state): State<DoubleBlindState>,
+  Json(data): Json<RollbackSite>,
+) -> Result<StatusCode, StatusCode> {
+  let repo = authorized_repository(&state, &session, data.github_id).await?;
+
+  let domain = match (repo.deployed, &repo.domain) {
+    (true, Some(domain)) => domain.clone(),
+    _ => return Err(StatusCode::BAD_REQUEST),
+  };
+
+  match state
+    .deployment_service
+    .rollback(&repo, &domain, &data.commit_id)
+    .await
+  {
+    Ok(Rollback::Activated) => Ok(StatusCode::OK),
+    Ok(Rollback::Missing) => {
+      info!(
+        "no retained release of {} for {}",
+        &data.commit_id, &repo.github_full_name
+      );
+      Err(StatusCode::NOT_FOUND)
+    }
+    Ok(Rollback::Mismatched) => {
+      info!(
+        "release of {} for {} was not built for the current camera ready mode",
+        &data.commit_id, &repo.github_full_name
+      );
+      Err(StatusCode::CONFLICT)
+    }
+    Err(e) => {
+      error!("error while rolling back {e}");
+      Err(StatusCode::INTERNAL_SERVER_ERROR)
+    }
+  }
+}
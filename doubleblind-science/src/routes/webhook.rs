@@ -0,0 +1,352 @@
+use axum::extract::State;
+use axum::http::{HeaderMap, StatusCode};
+use serde::de::DeserializeOwned;
+use serde::Deserialize;
+use tracing::{error, info};
+
+use crate::auth::GithubWebhook;
+use crate::routes::deploy::push_event;
+use crate::routes::GithubRepoEdit;
+use crate::service::delivery::DeliveryClaim;
+use crate::state::DoubleBlindState;
+
+/// header naming the kind of event, the payload differs for each of them
+const EVENT_HEADER: &str = "X-GitHub-Event";
+/// header with the id of the delivery, it stays the same when github retries or redelivers it
+const DELIVERY_HEADER: &str = "X-GitHub-Delivery";
+
+#[derive(Deserialize)]
+pub(super) struct InstallationInformation {
+  id: i64,
+}
+
+#[derive(Deserialize)]
+pub(super) struct InstallationEvent {
+  action: String,
+  installation: InstallationInformation,
+  /// only sent when the app was installed for a selection of repositories
+  #[serde(default)]
+  repositories: Vec<GithubRepoEdit>,
+}
+
+#[derive(Deserialize)]
+pub(super) struct InstallationRepositoriesEvent {
+  installation: InstallationInformation,
+  repositories_added: Vec<GithubRepoEdit>,
+  repositories_removed: Vec<GithubRepoEdit>,
+}
+
+#[derive(Deserialize)]
+pub(super) struct RepositoryEvent {
+  action: String,
+  repository: GithubRepoEdit,
+  /// installation the repository belongs to now, it changes when it is transferred
+  installation: Option<InstallationInformation>,
+}
+
+/// Receives every event of the github app and hands it to the handler for its kind, unknown events
+/// are acknowledged so github does not report them as failed. Deliveries which were already
+/// processed are only acknowledged again.
+pub(super) async fn github_webhook(
+  State(state): State<DoubleBlindState>,
+  headers: HeaderMap,
+  GithubWebhook(payload): GithubWebhook<serde_json::Value>,
+) -> Result<StatusCode, StatusCode> {
+  let event = headers
+    .get(EVENT_HEADER)
+    .and_then(|value| value.to_str().ok())
+    .unwrap_or_default();
+  let delivery = match headers
+    .get(DELIVERY_HEADER)
+    .and_then(|value| value.to_str().ok())
+  {
+    Some(value) => value,
+    None => {
+      info!("github webhook without {DELIVERY_HEADER}, it cannot be deduplicated");
+      return dispatch(&state, event, payload).await;
+    }
+  };
+  let action = payload.get("action").and_then(serde_json::Value::as_str);
+
+  match state.delivery_service.claim(delivery, event, action).await {
+    Ok(DeliveryClaim::Claimed) => {}
+    Ok(DeliveryClaim::Duplicate(status)) => {
+      info!("delivery {delivery} was already received");
+      // a delivery which is still processed counts as accepted, there is nothing to redo yet
+      return Ok(
+        status
+          .and_then(|status| u16::try_from(status).ok())
+          .and_then(|status| StatusCode::from_u16(status).ok())
+          .unwrap_or(StatusCode::ACCEPTED),
+      );
+    }
+    Err(e) => {
+      error!("error while trying to record delivery {e}");
+      return Err(StatusCode::INTERNAL_SERVER_ERROR);
+    }
+  }
+
+  let result = dispatch(&state, event, payload).await;
+
+  let status = match result {
+    Ok(status) | Err(status) => status,
+  };
+  if let Err(e) = state
+    .delivery_service
+    .finish(delivery, status.as_u16())
+    .await
+  {
+    error!("error while trying to record outcome of delivery {delivery} {e}");
+  }
+
+  result
+}
+
+async fn dispatch(
+  state: &DoubleBlindState,
+  event: &str,
+  payload: serde_json::Value,
+) -> Result<StatusCode, StatusCode> {
+  match event {
+    "push" => push_event(state, parse(event, payload)?).await,
+    "installation" => installation_event(state, parse(event, payload)?).await,
+    "installation_repositories" => {
+      installation_repositories_event(state, parse(event, payload)?).await
+    }
+    "repository" => repository_event(state, parse(event, payload)?).await,
+    "ping" => {
+      info!("github sent a ping");
+      Ok(StatusCode::OK)
+    }
+    _ => {
+      info!("ignoring github event {event:?}");
+      Ok(StatusCode::NO_CONTENT)
+    }
+  }
+}
+
+fn parse<T: DeserializeOwned>(event: &str, payload: serde_json::Value) -> Result<T, StatusCode> {
+  serde_json::from_value(payload).map_err(|e| {
+    error!("cannot parse {event} event from github {e}");
+    StatusCode::BAD_REQUEST
+  })
+}
+
+async fn installation_event(
+  state: &DoubleBlindState,
+  event: InstallationEvent,
+) -> Result<StatusCode, StatusCode> {
+  match event.action.as_str() {
+    "created" => {
+      info!("github app installed as {}", event.installation.id);
+      sync_repositories(state, event.installation.id, event.repositories, vec![]).await
+    }
+    "suspend" | "unsuspend" => {
+      let suspended = event.action == "suspend";
+      info!("installation {} {}ed", event.installation.id, event.action);
+
+      let repositories = match state
+        .project_service
+        .set_github_app_suspended(event.installation.id, suspended)
+        .await
+      {
+        Ok(Some((_, repositories))) => repositories,
+        Ok(None) => return Ok(StatusCode::NO_CONTENT),
+        Err(e) => {
+          error!("error while trying to update installation {e}");
+          return Err(StatusCode::INTERNAL_SERVER_ERROR);
+        }
+      };
+
+      let result = match suspended {
+        true => state.deployment_service.suspend_sites(&repositories).await,
+        false => state.deployment_service.resume_sites(&repositories).await,
+      };
+      result.map_err(|e| {
+        error!("cannot {} sites of installation {e}", event.action);
+        StatusCode::INTERNAL_SERVER_ERROR
+      })?;
+
+      Ok(StatusCode::OK)
+    }
+    "deleted" => {
+      info!("github app uninstalled from {}", event.installation.id);
+
+      let repositories = match state
+        .project_service
+        .remove_github_app(event.installation.id)
+        .await
+      {
+        Ok(Some((_, repositories))) => repositories,
+        Ok(None) => return Ok(StatusCode::NO_CONTENT),
+        Err(e) => {
+          error!("error while trying to update installation {e}");
+          return Err(StatusCode::INTERNAL_SERVER_ERROR);
+        }
+      };
+
+      state
+        .deployment_service
+        .remove_sites(&repositories)
+        .await
+        .map_err(|e| {
+          error!("cannot remove sites of installation {e}");
+          StatusCode::INTERNAL_SERVER_ERROR
+        })?;
+
+      Ok(StatusCode::OK)
+    }
+    action => {
+      info!(
+        "ignoring installation {} event of {}",
+        action, event.installation.id
+      );
+      Ok(StatusCode::NO_CONTENT)
+    }
+  }
+}
+
+async fn installation_repositories_event(
+  state: &DoubleBlindState,
+  event: InstallationRepositoriesEvent,
+) -> Result<StatusCode, StatusCode> {
+  info!(
+    "repositories of installation {} changed",
+    event.installation.id
+  );
+
+  sync_repositories(
+    state,
+    event.installation.id,
+    event.repositories_added,
+    event.repositories_removed,
+  )
+  .await
+}
+
+async fn repository_event(
+  state: &DoubleBlindState,
+  event: RepositoryEvent,
+) -> Result<StatusCode, StatusCode> {
+  match event.action.as_str() {
+    "deleted" => {
+      info!("repository {} was deleted", event.repository.full_name);
+      remove_repositories(state, vec![event.repository]).await?;
+      Ok(StatusCode::OK)
+    }
+    "renamed" | "transferred" => {
+      info!(
+        "repository {} {} was {}",
+        event.repository.id, event.repository.full_name, event.action
+      );
+
+      match state
+        .project_service
+        .update_repository(
+          event.repository,
+          event.installation.map(|installation| installation.id),
+        )
+        .await
+      {
+        Ok(Some(_)) => Ok(StatusCode::OK),
+        Ok(None) => Ok(StatusCode::NO_CONTENT),
+        Err(e) => {
+          error!("error while trying to update repository {e}");
+          Err(StatusCode::INTERNAL_SERVER_ERROR)
+        }
+      }
+    }
+    action => {
+      info!(
+        "ignoring repository {} event of {}",
+        action, event.repository.full_name
+      );
+      Ok(StatusCode::NO_CONTENT)
+    }
+  }
+}
+
+/// Adds and removes repositories of an installation, the repository list of its users waits until
+/// this is done.
+async fn sync_repositories(
+  state: &DoubleBlindState,
+  installation_id: i64,
+  added: Vec<GithubRepoEdit>,
+  removed: Vec<GithubRepoEdit>,
+) -> Result<StatusCode, StatusCode> {
+  state
+    .repos_per_installation
+    .write()
+    .await
+    .push(installation_id);
+
+  let result = async {
+    // create if github_app doesn't exist yet
+    let github_app = state
+      .project_service
+      .create_github_app(installation_id)
+      .await
+      .map_err(|e| {
+        error!("error when trying to create github app {e}");
+        StatusCode::INTERNAL_SERVER_ERROR
+      })?;
+
+    remove_repositories(state, removed).await?;
+
+    state
+      .project_service
+      .rewrite_list_of_repositories(github_app.id, added, vec![])
+      .await
+      .map_err(|e| {
+        error!("error while trying to rewrite repo list {e}");
+        StatusCode::INTERNAL_SERVER_ERROR
+      })
+  }
+  .await;
+
+  // also after a failure, otherwise the repository list of the installation would never load
+  state
+    .repos_per_installation
+    .write()
+    .await
+    .retain(|&item| item != installation_id);
+
+  result.map(|_| StatusCode::OK)
+}
+
+/// Takes the sites of the repositories offline before they are forgotten.
+async fn remove_repositories(
+  state: &DoubleBlindState,
+  repositories: Vec<GithubRepoEdit>,
+) -> Result<(), StatusCode> {
+  for repository in &repositories {
+    let model = match state.project_service.get_repository(repository.id).await {
+      Ok(Some(value)) => value,
+      Ok(None) => continue,
+      Err(e) => {
+        error!("error while trying to query repo {e}");
+        return Err(StatusCode::INTERNAL_SERVER_ERROR);
+      }
+    };
+
+    if model.deployed {
+      state
+        .deployment_service
+        .unpublish(&model)
+        .await
+        .map_err(|e| {
+          error!("cannot unpublish {} {e}", model.github_full_name);
+          StatusCode::INTERNAL_SERVER_ERROR
+        })?;
+    }
+  }
+
+  state
+    .project_service
+    .remove_repositories(repositories)
+    .await
+    .map_err(|e| {
+      error!("error while trying to remove repositories {e}");
+      StatusCode::INTERNAL_SERVER_ERROR
+    })
+}
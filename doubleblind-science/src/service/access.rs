@@ -0,0 +1,318 @@
+use std::sync::Arc;
+
+use anyhow::anyhow;
+use hmac::{Hmac, Mac};
+use rand::Rng;
+use sea_orm::entity::EntityTrait;
+use sea_orm::{ActiveModelTrait, ColumnTrait, DatabaseConnection, QueryFilter, QueryOrder, Set};
+use sea_query::{Expr, Func};
+use sha2::{Digest, Sha256};
+use time::{Duration, OffsetDateTime};
+use uuid::Uuid;
+
+use entity::repository::SiteFallback;
+use entity::{access_code, custom_domain, repository};
+
+/// cookie proving that a visitor entered a valid code for the site
+pub(crate) const ACCESS_COOKIE: &str = "doubleblind_access";
+/// how long a reviewer stays signed in to a site
+pub(crate) const ACCESS_LIFETIME: Duration = Duration::days(30);
+/// characters of a generated code, without the ones which are easily confused
+const CODE_ALPHABET: &[u8] = b"ABCDEFGHJKMNPQRSTUVWXYZ23456789";
+/// characters of a generated code, about 100 bit
+const CODE_LENGTH: usize = 20;
+/// characters between the dashes of a displayed code
+const CODE_GROUP: usize = 5;
+/// longest description of a code
+const MAX_LABEL_LENGTH: usize = 100;
+
+/// What a visitor gets to see of a site.
+#[derive(Debug, PartialEq, Eq)]
+pub(crate) enum SiteAccess {
+  /// carries what the site serves for paths without a file
+  Granted(SiteFallback),
+  /// the visitor has to enter one of the access codes of the site first
+  CodeRequired,
+  /// the site is not published right now
+  Hidden,
+}
+
+/// Manages the codes reviewers need to see a protected site and the cookies they get for them.
+#[derive(Clone)]
+pub(crate) struct AccessService {
+  db: Arc<DatabaseConnection>,
+  /// signs the cookies of reviewers, without it no codes can be created or redeemed
+  secret: Option<Arc<Vec<u8>>>,
+  root_domain: String,
+}
+
+impl AccessService {
+  pub(crate) fn new(
+    db: Arc<DatabaseConnection>,
+    secret: Option<Vec<u8>>,
+    root_domain: String,
+  ) -> Self {
+    Self {
+      db,
+      secret: secret.map(Arc::new),
+      root_domain,
+    }
+  }
+
+  pub(crate) fn codes_enabled(&self) -> bool {
+    self.secret.is_some()
+  }
+
+  /// Subdomain of the site a request is for, either directly below the root domain or through one
+  /// of the verified custom domains.
+  pub(crate) async fn site_domain(&self, host: &str) -> anyhow::Result<Option<String>> {
+    let host = match host.split(':').next() {
+      Some(value) => value.trim_end_matches('.').to_lowercase(),
+      None => return Ok(None),
+    };
+
+    if let Some(domain) = host.strip_suffix(&format!(".{}", self.root_domain.to_lowercase())) {
+      return Ok((!domain.is_empty() && !domain.contains('.')).then(|| domain.to_string()));
+    }
+
+    Ok(
+      custom_domain::Entity::find()
+        .filter(custom_domain::Column::Hostname.eq(host))
+        .filter(custom_domain::Column::VerifiedAt.is_not_null())
+        .find_also_related(repository::Entity)
+        .one(&*self.db)
+        .await?
+        .and_then(|(_, repository)| repository?.domain),
+    )
+  }
+
+  pub(crate) async fn codes(&self, repository: Uuid) -> anyhow::Result<Vec<access_code::Model>> {
+    Ok(
+      access_code::Entity::find()
+        .filter(access_code::Column::Repository.eq(repository))
+        .order_by_asc(access_code::Column::CreatedAt)
+        .all(&*self.db)
+        .await?,
+    )
+  }
+
+  /// Creates a new code for the repository, the code itself is only returned here and never stored.
+  pub(crate) async fn create_code(
+    &self,
+    repository: Uuid,
+    label: String,
+  ) -> anyhow::Result<(access_code::Model, String)> {
+    let code = generate_code();
+
+    let model = access_code::ActiveModel {
+      id: Set(Uuid::new_v4()),
+      repository: Set(repository),
+      label: Set(label),
+      code_hash: Set(hash_code(&code)),
+      created_at: Set(OffsetDateTime::now_utc()),
+    }
+    .insert(&*self.db)
+    .await?;
+
+    Ok((model, code))
+  }
+
+  /// Revokes a code together with every cookie issued for it, returns false if it does not exist.
+  pub(crate) async fn delete_code(&self, repository: Uuid, id: Uuid) -> anyhow::Result<bool> {
+    Ok(
+      access_code::Entity::delete_many()
+        .filter(access_code::Column::Id.eq(id))
+        .filter(access_code::Column::Repository.eq(repository))
+        .exec(&*self.db)
+        .await?
+        .rows_affected
+        > 0,
+    )
+  }
+
+  /// Repository published under the subdomain.
+  async fn site(&self, domain: &str) -> anyhow::Result<Option<repository::Model>> {
+    Ok(
+      repository::Entity::find()
+        .filter(Expr::expr(Func::lower(Expr::col(repository::Column::Domain))).eq(domain))
+        .one(&*self.db)
+        .await?,
+    )
+  }
+
+  /// Decides what a visitor gets to see of the site, sites without codes are public once they are
+  /// published.
+  pub(crate) async fn authorize(
+    &self,
+    domain: &str,
+    cookie: Option<&str>,
+  ) -> anyhow::Result<SiteAccess> {
+    let now = OffsetDateTime::now_utc();
+
+    let repository = match self.site(domain).await? {
+      Some(value) => value,
+      None => return Ok(SiteAccess::Hidden),
+    };
+
+    if !repository.deployed || repository.publish_after.is_some_and(|time| time > now) {
+      return Ok(SiteAccess::Hidden);
+    }
+
+    let codes = self.codes(repository.id).await?;
+    if codes.is_empty() {
+      return Ok(SiteAccess::Granted(repository.fallback));
+    }
+
+    // sites which got codes before they were disabled stay closed
+    let granted = cookie
+      .zip(self.secret.as_deref())
+      .and_then(|(cookie, secret)| verify_cookie(secret, domain, cookie, now))
+      .is_some_and(|code_id| codes.iter().any(|code| code.id == code_id));
+
+    Ok(match granted {
+      true => SiteAccess::Granted(repository.fallback),
+      false => SiteAccess::CodeRequired,
+    })
+  }
+
+  /// Returns the cookie value for the site if the code belongs to it.
+  pub(crate) async fn redeem(&self, domain: &str, code: &str) -> anyhow::Result<Option<String>> {
+    let secret = match &self.secret {
+      Some(value) => value,
+      None => return Ok(None),
+    };
+    let repository = match self.site(domain).await? {
+      Some(value) => value,
+      None => return Ok(None),
+    };
+    let hash = hash_code(code);
+
+    Ok(
+      self
+        .codes(repository.id)
+        .await?
+        .into_iter()
+        .find(|access_code| access_code.code_hash == hash)
+        .map(|access_code| {
+          cookie_value(
+            secret,
+            domain,
+            access_code.id,
+            OffsetDateTime::now_utc() + ACCESS_LIFETIME,
+          )
+        }),
+    )
+  }
+}
+
+/// Trims the description of a code and rejects empty or overly long ones.
+pub(crate) fn validate_label(label: &str) -> anyhow::Result<String> {
+  let label = label.trim();
+
+  if label.is_empty() || label.chars().count() > MAX_LABEL_LENGTH {
+    return Err(anyhow!(
+      "label has to be between 1 and {MAX_LABEL_LENGTH} characters long"
+    ));
+  }
+
+  Ok(label.to_string())
+}
+
+fn generate_code() -> String {
+  let mut rng = rand::thread_rng();
+
+  (0..CODE_LENGTH)
+    .map(|i| {
+      let c = CODE_ALPHABET[rng.gen_range(0..CODE_ALPHABET.len())] as char;
+      if i > 0 && i % CODE_GROUP == 0 {
+        format!("-{c}")
+      } else {
+        c.to_string()
+      }
+    })
+    .collect()
+}
+
+/// Codes are random and long enough that an unsalted hash cannot be reversed, dashes, whitespace and
+/// case are ignored.
+fn hash_code(code: &str) -> String {
+  let normalized = code
+    .chars()
+    .filter(|c| c.is_ascii_alphanumeric())
+    .map(|c| c.to_ascii_uppercase())
+    .collect::<String>();
+
+  hex::encode(Sha256::digest(normalized.as_bytes()))
+}
+
+fn signature(secret: &[u8], domain: &str, code_id: Uuid, expires: i64) -> Hmac<Sha256> {
+  let mut mac = Hmac::<Sha256>::new_from_slice(secret).expect("hmac accepts keys of any size");
+  mac.update(format!("{domain}\n{code_id}\n{expires}").as_bytes());
+  mac
+}
+
+fn cookie_value(secret: &[u8], domain: &str, code_id: Uuid, expires: OffsetDateTime) -> String {
+  let expires = expires.unix_timestamp();
+  let mac = signature(secret, domain, code_id, expires).finalize();
+
+  format!("{code_id}.{expires}.{}", hex::encode(mac.into_bytes()))
+}
+
+/// Returns the code a cookie was issued for, if it belongs to the site and has not expired.
+fn verify_cookie(secret: &[u8], domain: &str, value: &str, now: OffsetDateTime) -> Option<Uuid> {
+  let mut parts = value.splitn(3, '.');
+  let code_id = Uuid::parse_str(parts.next()?).ok()?;
+  let expires = parts.next()?.parse::<i64>().ok()?;
+  let mac = hex::decode(parts.next()?).ok()?;
+
+  if expires <= now.unix_timestamp() {
+    return None;
+  }
+
+  signature(secret, domain, code_id, expires)
+    .verify_slice(&mac)
+    .ok()
+    .map(|_| code_id)
+}
+
+#[cfg(test)]
+mod tests {
+  use time::{Duration, OffsetDateTime};
+  use uuid::Uuid;
+
+  use crate::service::access::{cookie_value, generate_code, hash_code, verify_cookie};
+
+  #[test]
+  fn test_hashes_normalized_code() {
+    let code = generate_code();
+
+    assert_eq!(code.len(), 23);
+    assert_eq!(code.matches('-').count(), 3);
+    assert_eq!(
+      hash_code(&code),
+      hash_code(&code.replace('-', "").to_lowercase())
+    );
+    assert_eq!(hash_code(&code), hash_code(&format!(" {code} ")));
+    assert_ne!(hash_code(&code), hash_code(&generate_code()));
+  }
+
+  #[test]
+  fn test_verifies_cookie() {
+    let secret = b"secret";
+    let code_id = Uuid::new_v4();
+    let now = OffsetDateTime::now_utc();
+    let cookie = cookie_value(secret, "paper", code_id, now + Duration::days(1));
+
+    assert_eq!(verify_cookie(secret, "paper", &cookie, now), Some(code_id));
+    assert_eq!(verify_cookie(b"other", "paper", &cookie, now), None);
+    assert_eq!(verify_cookie(secret, "other", &cookie, now), None);
+    assert_eq!(
+      verify_cookie(secret, "paper", &cookie, now + Duration::days(2)),
+      None
+    );
+
+    let forged = cookie.replacen(&code_id.to_string(), &Uuid::new_v4().to_string(), 1);
+    assert_eq!(verify_cookie(secret, "paper", &forged, now), None);
+    assert_eq!(verify_cookie(secret, "paper", "garbage", now), None);
+  }
+}
@@ -0,0 +1,228 @@
+use std::borrow::Cow;
+use std::collections::HashSet;
+use std::path::{Path, PathBuf};
+
+use anyhow::anyhow;
+use regex::bytes::{Regex, RegexBuilder};
+
+/// text every identity string is replaced with
+pub(crate) const PLACEHOLDER: &str = "[anonymized]";
+/// shorter terms would match inside of too many unrelated words
+pub(crate) const MIN_TERM_LENGTH: usize = 3;
+/// files containing a null byte in this many leading bytes are considered binary
+const BINARY_PROBE: usize = 8192;
+
+/// Replaces a fixed set of identity strings, ignoring case and also matching their url encoded form.
+pub(crate) struct Redactor {
+  pattern: Regex,
+}
+
+impl Redactor {
+  /// Returns `None` if there is nothing to redact.
+  pub(crate) fn new(terms: &[String]) -> anyhow::Result<Option<Self>> {
+    let mut variants = terms
+      .iter()
+      .filter(|term| !term.is_empty())
+      .flat_map(|term| {
+        // form encoding turns spaces into `+`, other encoders into `%20`
+        let form_encoded =
+          url::form_urlencoded::byte_serialize(term.as_bytes()).collect::<String>();
+        let percent_encoded = form_encoded.replace('+', "%20");
+        [term.clone(), form_encoded, percent_encoded]
+      })
+      .collect::<HashSet<String>>()
+      .into_iter()
+      .collect::<Vec<String>>();
+
+    if variants.is_empty() {
+      return Ok(None);
+    }
+
+    // alternatives are tried from left to right, longer ones have to win
+    variants.sort_by(|a, b| b.len().cmp(&a.len()).then_with(|| a.cmp(b)));
+
+    let pattern = RegexBuilder::new(
+      &variants
+        .iter()
+        .map(|variant| regex::escape(variant))
+        .collect::<Vec<String>>()
+        .join("|"),
+    )
+    .case_insensitive(true)
+    .build()?;
+
+    Ok(Some(Redactor { pattern }))
+  }
+
+  /// Returns the redacted content and how many replacements were made.
+  pub(crate) fn redact<'a>(&self, content: &'a [u8]) -> (Cow<'a, [u8]>, usize) {
+    let replacements = self.pattern.find_iter(content).count();
+    if replacements == 0 {
+      return (Cow::Borrowed(content), 0);
+    }
+
+    (
+      self.pattern.replace_all(content, PLACEHOLDER.as_bytes()),
+      replacements,
+    )
+  }
+}
+
+/// Trims the terms, drops empty and duplicate ones and rejects terms which are too short.
+pub(crate) fn validate_terms(terms: Vec<String>) -> anyhow::Result<Vec<String>> {
+  let mut seen = HashSet::new();
+  let mut validated = Vec::new();
+
+  for term in terms {
+    let term = term.trim();
+    if term.is_empty() {
+      continue;
+    }
+
+    if term.chars().count() < MIN_TERM_LENGTH {
+      return Err(anyhow!(
+        "{term} is shorter than {MIN_TERM_LENGTH} characters"
+      ));
+    }
+
+    if seen.insert(term.to_lowercase()) {
+      validated.push(term.to_string());
+    }
+  }
+
+  Ok(validated)
+}
+
+/// Redacts every text file below `root` in place, returns the files which were changed together
+/// with the number of replacements, relative to `root`. Symlinks are never followed.
+pub(crate) async fn redact_tree(
+  root: &Path,
+  redactor: &Redactor,
+) -> anyhow::Result<Vec<(PathBuf, usize)>> {
+  let mut report = Vec::new();
+  let mut directories = vec![root.to_path_buf()];
+
+  while let Some(directory) = directories.pop() {
+    let mut entries = tokio::fs::read_dir(&directory).await?;
+
+    while let Some(entry) = entries.next_entry().await? {
+      let file_type = entry.file_type().await?;
+      let path = entry.path();
+
+      if file_type.is_dir() {
+        directories.push(path);
+        continue;
+      }
+
+      if !file_type.is_file() {
+        continue;
+      }
+
+      let content = tokio::fs::read(&path).await?;
+      if content[..content.len().min(BINARY_PROBE)].contains(&0) {
+        continue;
+      }
+
+      let (redacted, replacements) = redactor.redact(&content);
+      if replacements == 0 {
+        continue;
+      }
+
+      tokio::fs::write(&path, &redacted).await?;
+      report.push((path.strip_prefix(root)?.to_path_buf(), replacements));
+    }
+  }
+
+  report.sort();
+
+  Ok(report)
+}
+
+#[cfg(test)]
+mod tests {
+  use std::path::PathBuf;
+
+  use crate::service::anonymize::{redact_tree, validate_terms, Redactor};
+
+  fn redactor(terms: &[&str]) -> Redactor {
+    Redactor::new(
+      &terms
+        .iter()
+        .map(|term| term.to_string())
+        .collect::<Vec<_>>(),
+    )
+    .unwrap()
+    .unwrap()
+  }
+
+  #[test]
+  fn test_redacts_case_insensitive() {
+    let redactor = redactor(&["Jane Doe", "jdoe"]);
+
+    let (redacted, replacements) =
+      redactor.redact(b"Copyright JANE DOE <jdoe@example.org>, maintained by jane doe");
+
+    assert_eq!(replacements, 3);
+    assert_eq!(
+      String::from_utf8_lossy(&redacted),
+      "Copyright [anonymized] <[anonymized]@example.org>, maintained by [anonymized]"
+    );
+  }
+
+  #[test]
+  fn test_redacts_urls() {
+    let redactor = redactor(&["Jane Doe", "Universität Dresden"]);
+
+    let (redacted, replacements) = redactor.redact(
+      b"https://example.org/?q=jane+doe https://example.org/Jane%20Doe https://x.org/universit%C3%A4t%20dresden",
+    );
+
+    assert_eq!(replacements, 3);
+    assert_eq!(
+      String::from_utf8_lossy(&redacted),
+      "https://example.org/?q=[anonymized] https://example.org/[anonymized] https://x.org/[anonymized]"
+    );
+  }
+
+  #[test]
+  fn test_validates_terms() {
+    assert_eq!(
+      validate_terms(vec![
+        " Jane Doe ".to_string(),
+        "jane doe".to_string(),
+        String::new()
+      ])
+      .unwrap(),
+      vec!["Jane Doe".to_string()]
+    );
+    assert!(validate_terms(vec!["JD".to_string()]).is_err());
+    assert!(Redactor::new(&[]).unwrap().is_none());
+  }
+
+  #[tokio::test]
+  async fn test_redacts_text_files_only() {
+    let dir = tempfile::tempdir().unwrap();
+    let root = dir.path();
+    tokio::fs::create_dir_all(root.join("src")).await.unwrap();
+    tokio::fs::write(root.join("src/main.rs"), "// by Jane Doe")
+      .await
+      .unwrap();
+    tokio::fs::write(root.join("logo.png"), b"\x89PNG\0Jane Doe")
+      .await
+      .unwrap();
+
+    let report = redact_tree(root, &redactor(&["jane doe"])).await.unwrap();
+
+    assert_eq!(report, vec![(PathBuf::from("src/main.rs"), 1)]);
+    assert_eq!(
+      tokio::fs::read_to_string(root.join("src/main.rs"))
+        .await
+        .unwrap(),
+      "// by [anonymized]"
+    );
+    assert_eq!(
+      tokio::fs::read(root.join("logo.png")).await.unwrap(),
+      b"\x89PNG\0Jane Doe"
+    );
+  }
+}
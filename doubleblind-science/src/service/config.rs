@@ -0,0 +1,134 @@
+use std::io;
+use std::path::{Path, PathBuf};
+
+use anyhow::anyhow;
+use serde::Deserialize;
+
+use crate::service::anonymize::validate_terms;
+use crate::service::exclude::ExcludeList;
+use crate::service::extract::subdirectory;
+
+/// file in the root of a repository describing how it is published
+pub(crate) const CONFIG_FILE: &str = ".doubleblind.toml";
+
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct RawRepositoryConfig {
+  #[serde(default)]
+  redact: Vec<String>,
+  #[serde(default)]
+  exclude: Vec<String>,
+  #[serde(default)]
+  keep: Vec<String>,
+  path: Option<String>,
+}
+
+/// validated content of the config file
+pub(crate) struct RepositoryConfig {
+  /// identity strings redacted in addition to the ones configured for the repository
+  pub(crate) redact: Vec<String>,
+  /// files and folders which are not published, relative to the published folder, including the
+  /// default excludes which are not kept
+  pub(crate) exclude: ExcludeList,
+  /// only the files and folders the repository excludes itself, camera-ready releases publish the
+  /// original content
+  pub(crate) camera_ready_exclude: ExcludeList,
+  /// folder which is published, overrides the one configured for the repository
+  pub(crate) path: Option<PathBuf>,
+}
+
+impl RepositoryConfig {
+  /// Reads the config from the root of an unpacked repository, the defaults if there is none.
+  pub(crate) async fn load(repository: &Path) -> anyhow::Result<Self> {
+    let file = repository.join(CONFIG_FILE);
+
+    match tokio::fs::symlink_metadata(&file).await {
+      Ok(metadata) if metadata.is_file() => {}
+      Ok(_) => return Err(anyhow!("{CONFIG_FILE} is not a regular file")),
+      Err(e) if e.kind() == io::ErrorKind::NotFound => return Self::parse(""),
+      Err(e) => return Err(e.into()),
+    }
+
+    let content = tokio::fs::read_to_string(&file)
+      .await
+      .map_err(|e| anyhow!("cannot read {CONFIG_FILE}: {e}"))?;
+
+    Self::parse(&content)
+  }
+
+  pub(crate) fn parse(content: &str) -> anyhow::Result<Self> {
+    let raw: RawRepositoryConfig =
+      toml::from_str(content).map_err(|e| anyhow!("invalid {CONFIG_FILE}: {e}"))?;
+
+    let redact =
+      validate_terms(raw.redact).map_err(|e| anyhow!("invalid {CONFIG_FILE}: redact: {e}"))?;
+
+    let exclude = ExcludeList::with_defaults(&raw.exclude, &raw.keep)
+      .map_err(|e| anyhow!("invalid {CONFIG_FILE}: {e}"))?;
+    let camera_ready_exclude =
+      ExcludeList::new(&raw.exclude).map_err(|e| anyhow!("invalid {CONFIG_FILE}: {e}"))?;
+
+    let path = match raw.path {
+      Some(path) => subdirectory(&path).map_err(|e| anyhow!("invalid {CONFIG_FILE}: path: {e}"))?,
+      None => None,
+    };
+
+    Ok(RepositoryConfig {
+      redact,
+      exclude,
+      camera_ready_exclude,
+      path,
+    })
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use std::path::{Path, PathBuf};
+
+  use crate::service::config::RepositoryConfig;
+
+  #[test]
+  fn test_parses_config() {
+    let config = RepositoryConfig::parse(
+      r#"
+      redact = ["Jane Doe", "TU Dresden"]
+      exclude = ["*.csv", "drafts/"]
+      keep = ["CITATION.cff"]
+      path = "/site/"
+      "#,
+    )
+    .unwrap();
+
+    assert_eq!(config.redact, vec!["Jane Doe", "TU Dresden"]);
+    assert!(config.exclude.matches(Path::new("data/a.csv"), false));
+    assert!(config.exclude.matches(Path::new(".github"), true));
+    assert!(!config.exclude.matches(Path::new("CITATION.cff"), false));
+    assert!(config
+      .camera_ready_exclude
+      .matches(Path::new("drafts"), true));
+    assert!(!config
+      .camera_ready_exclude
+      .matches(Path::new(".github"), true));
+    assert_eq!(config.path, Some(PathBuf::from("site")));
+
+    let empty = RepositoryConfig::parse("").unwrap();
+    assert!(empty.redact.is_empty() && !empty.exclude.is_empty());
+    assert!(empty.camera_ready_exclude.is_empty());
+    assert!(empty.path.is_none());
+  }
+
+  #[test]
+  fn test_reports_invalid_config() {
+    let error = |content: &str| format!("{:#}", RepositoryConfig::parse(content).err().unwrap());
+
+    assert!(error("redact = \"Jane Doe\"").contains("invalid type"));
+    assert!(error("exlude = [\"*.csv\"]").contains("unknown field"));
+    assert!(error("redact = [\"JD\"]").contains("redact"));
+    assert!(error("exclude = [\"../x\"]").contains("exclude"));
+    assert!(error("keep = [\"README.md\"]").contains("keep"));
+    assert!(error("path = \"../x\"").contains("path"));
+    // builds need a sandbox before repositories may run them
+    assert!(error("build = \"make html\"").contains("unknown field"));
+  }
+}
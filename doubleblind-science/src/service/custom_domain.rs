@@ -0,0 +1,192 @@
+use std::net::SocketAddr;
+use std::sync::Arc;
+
+use hickory_resolver::config::{NameServerConfigGroup, ResolverConfig, ResolverOpts};
+use hickory_resolver::error::ResolveErrorKind;
+use hickory_resolver::TokioAsyncResolver;
+use sea_orm::entity::EntityTrait;
+use sea_orm::{
+  ActiveModelTrait, ColumnTrait, DatabaseConnection, QueryFilter, QueryOrder, Set, Unchanged,
+};
+use time::OffsetDateTime;
+use tracing::info;
+use uuid::Uuid;
+
+use entity::custom_domain;
+
+use crate::service::domain::validate_hostname;
+
+/// label in front of the hostname which holds the txt record proving its ownership
+const CHALLENGE_LABEL: &str = "_doubleblind-challenge";
+/// prefix of the txt record, so that it is recognizable next to other records
+const CHALLENGE_PREFIX: &str = "doubleblind-verification=";
+
+/// Manages additional hostnames of repositories and verifies their ownership through dns.
+#[derive(Clone)]
+pub(crate) struct CustomDomainService {
+  db: Arc<DatabaseConnection>,
+  resolver: Arc<TokioAsyncResolver>,
+  root_domain: String,
+}
+
+impl CustomDomainService {
+  /// Queries the given name server, or the ones of the system if there is none.
+  pub(crate) fn new(
+    db: Arc<DatabaseConnection>,
+    name_server: Option<SocketAddr>,
+    root_domain: String,
+  ) -> anyhow::Result<Self> {
+    let (config, mut options) = match name_server {
+      Some(address) => (
+        ResolverConfig::from_parts(
+          None,
+          vec![],
+          NameServerConfigGroup::from_ips_clear(&[address.ip()], address.port(), true),
+        ),
+        ResolverOpts::default(),
+      ),
+      None => hickory_resolver::system_conf::read_system_conf()?,
+    };
+    // a record which was just created has to be visible on the next try
+    options.cache_size = 0;
+
+    Ok(Self {
+      db,
+      resolver: Arc::new(TokioAsyncResolver::tokio(config, options)),
+      root_domain,
+    })
+  }
+
+  /// Checks a hostname an author wants to add, see [`validate_hostname`].
+  pub(crate) fn validate(&self, hostname: &str) -> anyhow::Result<String> {
+    validate_hostname(hostname, &self.root_domain)
+  }
+
+  pub(crate) async fn domains(
+    &self,
+    repository: Uuid,
+  ) -> anyhow::Result<Vec<custom_domain::Model>> {
+    Ok(
+      custom_domain::Entity::find()
+        .filter(custom_domain::Column::Repository.eq(repository))
+        .order_by_asc(custom_domain::Column::CreatedAt)
+        .all(&*self.db)
+        .await?,
+    )
+  }
+
+  /// Adds an unverified hostname, which is only served once its txt record was found.
+  pub(crate) async fn add(
+    &self,
+    repository: Uuid,
+    hostname: String,
+  ) -> anyhow::Result<custom_domain::Model> {
+    Ok(
+      custom_domain::ActiveModel {
+        id: Set(Uuid::new_v4()),
+        repository: Set(repository),
+        hostname: Set(hostname),
+        token: Set(Uuid::new_v4().simple().to_string()),
+        verified_at: Set(None),
+        created_at: Set(OffsetDateTime::now_utc()),
+      }
+      .insert(&*self.db)
+      .await?,
+    )
+  }
+
+  /// Removes a hostname from the repository, returns it if it existed.
+  pub(crate) async fn delete(
+    &self,
+    repository: Uuid,
+    id: Uuid,
+  ) -> anyhow::Result<Option<custom_domain::Model>> {
+    let domain = match self.domain(repository, id).await? {
+      Some(value) => value,
+      None => return Ok(None),
+    };
+
+    custom_domain::Entity::delete_by_id(domain.id)
+      .exec(&*self.db)
+      .await?;
+
+    Ok(Some(domain))
+  }
+
+  /// Looks for the txt record of the hostname and marks it as verified if it contains the token.
+  pub(crate) async fn verify(
+    &self,
+    repository: Uuid,
+    id: Uuid,
+  ) -> anyhow::Result<Option<custom_domain::Model>> {
+    let domain = match self.domain(repository, id).await? {
+      Some(value) => value,
+      None => return Ok(None),
+    };
+
+    let expected = challenge_value(&domain.token);
+    let records = match self
+      .resolver
+      // fully qualified, so no search domain of the system is appended
+      .txt_lookup(format!("{}.", challenge_name(&domain.hostname)))
+      .await
+    {
+      Ok(lookup) => lookup
+        .iter()
+        .map(|txt| {
+          // long records are split into several strings of at most 255 bytes
+          txt
+            .txt_data()
+            .iter()
+            .map(|part| String::from_utf8_lossy(part))
+            .collect::<String>()
+        })
+        .collect::<Vec<String>>(),
+      Err(e) if matches!(e.kind(), ResolveErrorKind::NoRecordsFound { .. }) => Vec::new(),
+      Err(e) => return Err(e.into()),
+    };
+
+    if !records.iter().any(|record| record.trim() == expected) {
+      info!("no verification record found for {}", domain.hostname);
+      return Ok(Some(domain));
+    }
+
+    if domain.verified_at.is_some() {
+      return Ok(Some(domain));
+    }
+
+    info!("verified {}", domain.hostname);
+    Ok(Some(
+      custom_domain::ActiveModel {
+        id: Unchanged(domain.id),
+        verified_at: Set(Some(OffsetDateTime::now_utc())),
+        ..Default::default()
+      }
+      .update(&*self.db)
+      .await?,
+    ))
+  }
+
+  async fn domain(
+    &self,
+    repository: Uuid,
+    id: Uuid,
+  ) -> anyhow::Result<Option<custom_domain::Model>> {
+    Ok(
+      custom_domain::Entity::find_by_id(id)
+        .filter(custom_domain::Column::Repository.eq(repository))
+        .one(&*self.db)
+        .await?,
+    )
+  }
+}
+
+/// Name of the txt record an author has to create to prove that they own the hostname.
+pub(crate) fn challenge_name(hostname: &str) -> String {
+  format!("{CHALLENGE_LABEL}.{hostname}")
+}
+
+/// Content of the txt record proving ownership.
+pub(crate) fn challenge_value(token: &str) -> String {
+  format!("{CHALLENGE_PREFIX}{token}")
+}
@@ -0,0 +1,197 @@
+use std::sync::Arc;
+
+use anyhow::anyhow;
+
+use sea_orm::entity::EntityTrait;
+use sea_orm::{ColumnTrait, DatabaseConnection, QueryFilter, QueryOrder, QuerySelect, Set};
+use sea_query::{Expr, OnConflict};
+use time::{Duration, OffsetDateTime};
+use tracing::info;
+
+use entity::webhook_delivery;
+
+/// time after which a delivery that is still processed is assumed to be lost and taken over
+const CLAIM_TIMEOUT: Duration = Duration::minutes(10);
+/// github only offers to redeliver the webhooks of the last days
+const RETENTION: Duration = Duration::days(14);
+
+pub(crate) enum DeliveryClaim {
+  /// the delivery is new or failed before and has to be processed
+  Claimed,
+  /// the delivery was already answered with this status, or is processed right now if there is none
+  Duplicate(Option<i32>),
+}
+
+/// Remembers the webhook deliveries of github, so that retries and redeliveries are not processed
+/// twice.
+#[derive(Clone)]
+pub(crate) struct DeliveryService {
+  db: Arc<DatabaseConnection>,
+}
+
+impl DeliveryService {
+  pub(crate) fn new(db: Arc<DatabaseConnection>) -> Self {
+    Self { db }
+  }
+
+  /// Records that the delivery is processed now, unless it was already answered successfully or is
+  /// still processed by another request. github never retries on its own, so a delivery arriving
+  /// again was redelivered on purpose and is processed again if it failed before.
+  pub(crate) async fn claim(
+    &self,
+    id: &str,
+    event: &str,
+    action: Option<&str>,
+  ) -> anyhow::Result<DeliveryClaim> {
+    let now = OffsetDateTime::now_utc();
+
+    let inserted = webhook_delivery::Entity::insert(webhook_delivery::ActiveModel {
+      id: Set(id.to_string()),
+      event: Set(event.to_string()),
+      action: Set(action.map(str::to_string)),
+      status: Set(None),
+      attempts: Set(1),
+      duplicates: Set(0),
+      received_at: Set(now),
+      claimed_at: Set(now),
+      finished_at: Set(None),
+    })
+    .on_conflict(
+      OnConflict::column(webhook_delivery::Column::Id)
+        .do_nothing()
+        .to_owned(),
+    )
+    .exec_without_returning(&*self.db)
+    .await?;
+
+    if inserted > 0 {
+      webhook_delivery::Entity::delete_many()
+        .filter(webhook_delivery::Column::ReceivedAt.lt(now - RETENTION))
+        .exec(&*self.db)
+        .await?;
+
+      return Ok(DeliveryClaim::Claimed);
+    }
+
+    let delivery = webhook_delivery::Entity::find_by_id(id)
+      .one(&*self.db)
+      .await?
+      .ok_or_else(|| anyhow!("webhook delivery {id} vanished while claiming it"))?;
+
+    if is_retried(&delivery, now) {
+      // of several redeliveries arriving at once only one gets to update the row
+      let retried = webhook_delivery::Entity::update_many()
+        .col_expr(webhook_delivery::Column::Status, Expr::value(None::<i32>))
+        .col_expr(
+          webhook_delivery::Column::Attempts,
+          Expr::col(webhook_delivery::Column::Attempts).add(1),
+        )
+        .col_expr(webhook_delivery::Column::ClaimedAt, Expr::value(now))
+        .col_expr(
+          webhook_delivery::Column::FinishedAt,
+          Expr::value(None::<OffsetDateTime>),
+        )
+        .filter(webhook_delivery::Column::Id.eq(id))
+        .filter(webhook_delivery::Column::Attempts.eq(delivery.attempts))
+        .exec(&*self.db)
+        .await?;
+
+      if retried.rows_affected > 0 {
+        info!("retrying webhook delivery {id}");
+        return Ok(DeliveryClaim::Claimed);
+      }
+    }
+
+    webhook_delivery::Entity::update_many()
+      .col_expr(
+        webhook_delivery::Column::Duplicates,
+        Expr::col(webhook_delivery::Column::Duplicates).add(1),
+      )
+      .filter(webhook_delivery::Column::Id.eq(id))
+      .exec(&*self.db)
+      .await?;
+
+    let status = webhook_delivery::Entity::find_by_id(id)
+      .one(&*self.db)
+      .await?
+      .and_then(|delivery| delivery.status);
+
+    Ok(DeliveryClaim::Duplicate(status))
+  }
+
+  /// Stores the status the delivery was answered with.
+  pub(crate) async fn finish(&self, id: &str, status: u16) -> anyhow::Result<()> {
+    webhook_delivery::Entity::update_many()
+      .col_expr(
+        webhook_delivery::Column::Status,
+        Expr::value(i32::from(status)),
+      )
+      .col_expr(
+        webhook_delivery::Column::FinishedAt,
+        Expr::value(OffsetDateTime::now_utc()),
+      )
+      .filter(webhook_delivery::Column::Id.eq(id))
+      .exec(&*self.db)
+      .await?;
+
+    Ok(())
+  }
+
+  pub(crate) async fn recent(&self, limit: u64) -> anyhow::Result<Vec<webhook_delivery::Model>> {
+    Ok(
+      webhook_delivery::Entity::find()
+        .order_by_desc(webhook_delivery::Column::ReceivedAt)
+        .limit(limit)
+        .all(&*self.db)
+        .await?,
+    )
+  }
+}
+
+/// Whether a delivery which arrives again is processed once more, which is the case if it failed or
+/// the request processing it got lost.
+fn is_retried(delivery: &webhook_delivery::Model, now: OffsetDateTime) -> bool {
+  match delivery.status {
+    Some(status) => !(200..300).contains(&status),
+    None => delivery.claimed_at < now - CLAIM_TIMEOUT,
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use time::{Duration, OffsetDateTime};
+
+  use entity::webhook_delivery;
+
+  use crate::service::delivery::is_retried;
+
+  fn delivery(status: Option<i32>, claimed_at: OffsetDateTime) -> webhook_delivery::Model {
+    webhook_delivery::Model {
+      id: "72d3162e-cc78-11e3-81ab-4c9367dc0958".to_string(),
+      event: "installation".to_string(),
+      action: Some("created".to_string()),
+      status,
+      attempts: 1,
+      duplicates: 0,
+      received_at: claimed_at,
+      claimed_at,
+      finished_at: status.map(|_| claimed_at),
+    }
+  }
+
+  #[test]
+  fn test_retries_failed_deliveries() {
+    let now = OffsetDateTime::now_utc();
+    let recent = now - Duration::minutes(1);
+
+    assert!(!is_retried(&delivery(Some(200), recent), now));
+    assert!(!is_retried(&delivery(Some(204), recent), now));
+    // the installation may be known by now
+    assert!(is_retried(&delivery(Some(404), recent), now));
+    assert!(is_retried(&delivery(Some(400), recent), now));
+    assert!(is_retried(&delivery(Some(500), recent), now));
+
+    assert!(!is_retried(&delivery(None, recent), now));
+    assert!(is_retried(&delivery(None, now - Duration::hours(1)), now));
+  }
+}
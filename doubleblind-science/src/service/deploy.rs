@@ -1,124 +1,1513 @@
+use std::collections::HashSet;
 use std::io;
-use std::path::PathBuf;
+use std::os::unix::fs::MetadataExt;
+use std::path::{Path, PathBuf};
 use std::sync::Arc;
+use std::time::Duration;
 
+use anyhow::anyhow;
 use async_compression::tokio::bufread::GzipDecoder;
+use futures_util::future::try_join_all;
 use futures_util::StreamExt;
-use reqwest::Client;
-use tokio::sync::mpsc::{channel, Receiver, Sender};
-use tokio::sync::Mutex;
+use reqwest::{Client, StatusCode};
+use sea_orm::entity::EntityTrait;
+use sea_orm::{
+  ActiveModelTrait, ColumnTrait, ConnectionTrait, DatabaseConnection, DbBackend, QueryFilter,
+  QueryOrder, QuerySelect, Set, Statement, TransactionTrait, Unchanged,
+};
+use sea_query::Expr;
+use time::OffsetDateTime;
+use tokio::sync::Notify;
 use tokio_tar::Archive;
 use tokio_util::io::StreamReader;
-use tracing::info;
+use tracing::{error, info, warn};
+use uuid::Uuid;
+
+use entity::deployment::{DeploymentStatus, DeploymentTrigger};
+use entity::repository::CameraReadyLink;
+use entity::{custom_domain, deployment, github_app, redaction_report, repository};
+
+use crate::service::anonymize::{redact_tree, Redactor};
+use crate::service::config::RepositoryConfig;
+use crate::service::exclude::remove_excluded;
+use crate::service::extract::{extract_archive, site_root, ExtractionLimits};
+use crate::service::github_app::ProjectService;
+use crate::service::metadata::scrub_tree;
+use crate::service::redirects::RedirectRules;
+use crate::service::reveal::{insert_banner, write_redirect};
+use crate::service::token::TokenService;
+
+/// folder inside the webroot which contains every release, grouped by site
+const RELEASE_DIR: &str = ".releases";
+/// folder inside the webroot where tarballs are unpacked before they become a release
+const STAGING_DIR: &str = ".staging";
+/// how often a deployment is tried before it is marked as failed
+const MAX_ATTEMPTS: u32 = 3;
+/// delay before the first retry, doubled after every further attempt
+const RETRY_BACKOFF: Duration = Duration::from_secs(10);
+/// how long an idle deploy loop waits before looking for queued deployments again
+const POLL_INTERVAL: Duration = Duration::from_secs(5);
+/// how long a deployment may take including its retries before it is marked as failed
+const DEPLOYMENT_TIMEOUT: Duration = Duration::from_secs(30 * 60);
+/// deployments running longer than this were interrupted, other instances never run them this long
+const DEPLOYMENT_LEASE: Duration = Duration::from_secs(40 * 60);
+/// key of the postgres advisory lock taken while claiming a deployment
+const CLAIM_LOCK: i64 = 0x646f75626c65;
+
+/// What happens to the sites of an installation which was suspended or uninstalled on github.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, clap::ValueEnum)]
+pub(crate) enum SuspensionPolicy {
+  /// sites go offline and come back once the installation is unsuspended
+  Unpublish,
+  /// sites stay online as they are, but are not deployed anymore
+  Freeze,
+}
+
+#[derive(Debug, PartialEq, Eq)]
+pub(crate) enum Rollback {
+  Activated,
+  /// there is no retained release of the commit
+  Missing,
+  /// the retained releases of the commit were built for the other camera ready mode
+  Mismatched,
+}
 
 pub(crate) struct DeploymentInformation {
-  pub(crate) full_name: String,
-  pub(crate) token: String,
+  pub(crate) repository: Uuid,
   pub(crate) commit_id: String,
-  pub(crate) domain: String,
+  pub(crate) trigger: DeploymentTrigger,
+}
+
+/// everything needed to fetch and publish a claimed deployment
+struct DeploymentJob {
+  id: Uuid,
+  repository: Uuid,
+  full_name: String,
+  token: String,
+  commit_id: String,
+  domain: String,
+  path: Option<PathBuf>,
+  quota: u64,
+  redactions: Vec<String>,
+  /// publishes the original content instead of the anonymized one
+  camera_ready: bool,
+  /// how a camera ready site points to the real repository
+  link: Option<CameraReadyLink>,
+  /// the release is only activated once the publish date of the site is reached
+  scheduled: bool,
 }
 
 #[derive(Clone)]
 pub(crate) struct DeploymentService {
+  db: Arc<DatabaseConnection>,
+  project_service: ProjectService,
+  token_service: TokenService,
   client: Client,
   webroot: PathBuf,
   root_domain: String,
-  queue_receiver: Arc<Mutex<Receiver<DeploymentInformation>>>,
-  queue_sender: Arc<Mutex<Sender<DeploymentInformation>>>,
+  history: u64,
+  limits: ExtractionLimits,
+  default_quota: u64,
+  suspension: SuspensionPolicy,
+  queue_notify: Arc<Notify>,
 }
 
 impl DeploymentService {
-  pub(crate) fn new(webroot: PathBuf, root_domain: String) -> Self {
-    let (queue_sender, queue_receiver) = channel::<DeploymentInformation>(500);
+  #[allow(clippy::too_many_arguments)]
+  pub(crate) fn new(
+    db: Arc<DatabaseConnection>,
+    project_service: ProjectService,
+    token_service: TokenService,
+    webroot: PathBuf,
+    root_domain: String,
+    history: u64,
+    limits: ExtractionLimits,
+    default_quota: u64,
+    suspension: SuspensionPolicy,
+  ) -> Self {
     Self {
+      db,
+      project_service,
+      token_service,
       client: Client::new(),
       webroot,
       root_domain,
-      queue_receiver: Arc::new(Mutex::new(queue_receiver)),
-      queue_sender: Arc::new(Mutex::new(queue_sender)),
+      history,
+      limits,
+      default_quota,
+      suspension,
+      queue_notify: Arc::new(Notify::new()),
     }
   }
 
-  pub(crate) async fn queue_deployment(
-    &mut self,
-    data: DeploymentInformation,
+  /// Folder, or symlink to the active release, which is served for the subdomain.
+  pub(crate) fn site_directory(&self, domain: &str) -> PathBuf {
+    self
+      .webroot
+      .join(format!("{}.{}", domain, self.root_domain))
+  }
+
+  /// Bytes the repositories of an installation may occupy each, including their retained releases.
+  pub(crate) fn quota(&self, github_app: &github_app::Model) -> u64 {
+    github_app
+      .quota
+      .map(|quota| quota.max(0) as u64)
+      .unwrap_or(self.default_quota)
+  }
+
+  /// Records the deployment as queued, it is picked up by the next free deploy loop. Deployments of
+  /// the same repository which are still waiting are superseded by it.
+  pub(crate) async fn queue_deployment(&self, data: DeploymentInformation) -> anyhow::Result<Uuid> {
+    let id = Uuid::new_v4();
+    let now = OffsetDateTime::now_utc();
+    let txn = self.db.begin().await?;
+
+    deployment::Entity::update_many()
+      .col_expr(
+        deployment::Column::Status,
+        Expr::value(DeploymentStatus::Superseded),
+      )
+      .col_expr(deployment::Column::FinishedAt, Expr::value(now))
+      .filter(deployment::Column::Repository.eq(data.repository))
+      .filter(deployment::Column::Status.eq(DeploymentStatus::Queued))
+      .exec(&txn)
+      .await?;
+
+    deployment::ActiveModel {
+      id: Set(id),
+      repository: Set(data.repository),
+      commit_id: Set(data.commit_id),
+      status: Set(DeploymentStatus::Queued),
+      trigger: Set(data.trigger),
+      release: Set(None),
+      retained: Set(false),
+      error: Set(None),
+      size: Set(None),
+      anonymized: Set(None),
+      created_at: Set(now),
+      started_at: Set(None),
+      finished_at: Set(None),
+    }
+    .insert(&txn)
+    .await?;
+
+    txn.commit().await?;
+
+    self.queue_notify.notify_one();
+
+    Ok(id)
+  }
+
+  /// Puts deployments back into the queue whose lease ran out, because the server running them
+  /// stopped. Deployments which are still running on another instance are left alone.
+  pub(crate) async fn requeue_interrupted(&self) -> anyhow::Result<u64> {
+    let expired = OffsetDateTime::now_utc() - DEPLOYMENT_LEASE;
+
+    Ok(
+      deployment::Entity::update_many()
+        .col_expr(
+          deployment::Column::Status,
+          Expr::value(DeploymentStatus::Queued),
+        )
+        .col_expr(
+          deployment::Column::StartedAt,
+          Expr::value(Option::<OffsetDateTime>::None),
+        )
+        .filter(deployment::Column::Status.eq(DeploymentStatus::Running))
+        .filter(deployment::Column::StartedAt.lt(expired))
+        .exec(&*self.db)
+        .await?
+        .rows_affected,
+    )
+  }
+
+  /// Files changed by the anonymization of a deployment, `None` if it is not part of the repository.
+  pub(crate) async fn redaction_report(
+    &self,
+    repository: Uuid,
+    id: Uuid,
+  ) -> anyhow::Result<Option<Vec<redaction_report::Model>>> {
+    if deployment::Entity::find_by_id(id)
+      .filter(deployment::Column::Repository.eq(repository))
+      .one(&*self.db)
+      .await?
+      .is_none()
+    {
+      return Ok(None);
+    }
+
+    Ok(Some(
+      redaction_report::Entity::find()
+        .filter(redaction_report::Column::Deployment.eq(id))
+        .order_by_asc(redaction_report::Column::Path)
+        .all(&*self.db)
+        .await?,
+    ))
+  }
+
+  pub(crate) async fn deployments(
+    &self,
+    repository: Uuid,
+  ) -> anyhow::Result<Vec<deployment::Model>> {
+    Ok(
+      deployment::Entity::find()
+        .filter(deployment::Column::Repository.eq(repository))
+        .order_by_desc(deployment::Column::CreatedAt)
+        .limit(100)
+        .all(&*self.db)
+        .await?,
+    )
+  }
+
+  /// Commit of the release which was published last, `None` if nothing was published yet.
+  pub(crate) async fn current_commit(&self, repository: Uuid) -> anyhow::Result<Option<String>> {
+    Ok(
+      deployment::Entity::find()
+        .filter(deployment::Column::Repository.eq(repository))
+        .filter(deployment::Column::Status.eq(DeploymentStatus::Succeeded))
+        .order_by_desc(deployment::Column::FinishedAt)
+        .one(&*self.db)
+        .await?
+        .map(|deployment| deployment.commit_id),
+    )
+  }
+
+  /// Takes the site of the repository offline and deletes its releases, it can be deployed again.
+  pub(crate) async fn unpublish(&self, repository: &repository::Model) -> anyhow::Result<()> {
+    if let Some(domain) = &repository.domain {
+      let site = format!("{}.{}", domain, self.root_domain);
+      let link = self.webroot.join(&site);
+
+      match tokio::fs::symlink_metadata(&link).await {
+        Ok(metadata) if metadata.is_dir() => tokio::fs::remove_dir_all(&link).await?,
+        Ok(_) => tokio::fs::remove_file(&link).await?,
+        Err(e) if e.kind() == io::ErrorKind::NotFound => {}
+        Err(e) => return Err(e.into()),
+      }
+
+      remove_if_exists(&self.webroot.join(RELEASE_DIR).join(&site)).await;
+      info!("Unpublished {}", site);
+    }
+
+    for domain in self.verified_domains(repository.id).await? {
+      self.unlink_custom_domain(&domain.hostname).await?;
+    }
+
+    let txn = self.db.begin().await?;
+
+    deployment::Entity::update_many()
+      .col_expr(deployment::Column::Retained, Expr::value(false))
+      .filter(deployment::Column::Repository.eq(repository.id))
+      .exec(&txn)
+      .await?;
+
+    repository::ActiveModel {
+      id: Unchanged(repository.id),
+      deployed: Set(false),
+      disk_usage: Set(0),
+      ..Default::default()
+    }
+    .update(&txn)
+    .await?;
+
+    txn.commit().await?;
+
+    Ok(())
+  }
+
+  /// Moves the site and its releases from one domain to another, so it stays online and rollbacks
+  /// keep working. Nothing is served under the old domain anymore.
+  pub(crate) async fn move_site(
+    &self,
+    repository: Uuid,
+    from: &str,
+    to: &str,
+  ) -> anyhow::Result<()> {
+    let old_site = format!("{}.{}", from, self.root_domain);
+    let new_site = format!("{}.{}", to, self.root_domain);
+    let old_link = self.webroot.join(&old_site);
+    let old_releases = self.webroot.join(RELEASE_DIR).join(&old_site);
+    let new_releases = self.webroot.join(RELEASE_DIR).join(&new_site);
+
+    let active = match tokio::fs::symlink_metadata(&old_link).await {
+      Ok(metadata) if metadata.is_symlink() => {
+        let target = tokio::fs::read_link(&old_link).await?;
+        tokio::fs::remove_file(&old_link).await?;
+        target.file_name().map(PathBuf::from)
+      }
+      Ok(_) => {
+        // sites deployed before releases existed are plain directories
+        tokio::fs::rename(&old_link, self.webroot.join(&new_site)).await?;
+        None
+      }
+      Err(e) if e.kind() == io::ErrorKind::NotFound => None,
+      Err(e) => return Err(e.into()),
+    };
+
+    // the domain belongs to this repository now, anything left under it is stale
+    remove_if_exists(&new_releases).await;
+    let moved = match tokio::fs::rename(&old_releases, &new_releases).await {
+      Ok(()) => true,
+      Err(e) if e.kind() == io::ErrorKind::NotFound => false,
+      Err(e) => return Err(e.into()),
+    };
+
+    if let (true, Some(release)) = (moved, active) {
+      self
+        .activate_release(&new_site, &new_releases.join(release))
+        .await?;
+    }
+
+    self.link_custom_domains(repository, to).await?;
+    info!("Moved {} to {}", old_site, new_site);
+
+    Ok(())
+  }
+
+  /// Applies the suspension policy to the sites of a suspended installation, their releases are kept.
+  pub(crate) async fn suspend_sites(
+    &self,
+    repositories: &[repository::Model],
+  ) -> anyhow::Result<()> {
+    if self.suspension == SuspensionPolicy::Freeze {
+      return Ok(());
+    }
+
+    for repository in repositories.iter().filter(|repository| repository.deployed) {
+      self.take_offline(repository).await?;
+      info!("Suspended {}", repository.github_full_name);
+    }
+
+    Ok(())
+  }
+
+  /// Brings the sites of an unsuspended installation back online with their latest release, sites
+  /// waiting for their publish date stay offline.
+  pub(crate) async fn resume_sites(
+    &self,
+    repositories: &[repository::Model],
   ) -> anyhow::Result<()> {
-    Ok(self.queue_sender.lock().await.send(data).await?)
+    if self.suspension == SuspensionPolicy::Freeze {
+      return Ok(());
+    }
+
+    for repository in repositories.iter().filter(|repository| repository.deployed) {
+      if is_scheduled(repository.publish_after) {
+        continue;
+      }
+
+      if self.activate_latest(repository).await? {
+        info!("Resumed {}", repository.github_full_name);
+      }
+    }
+
+    Ok(())
+  }
+
+  /// Takes the site offline while its publish date lies ahead and brings it online once it is
+  /// reached, unless the installation is suspended.
+  pub(crate) async fn apply_schedule(&self, repository: &repository::Model) -> anyhow::Result<()> {
+    let domain = match (repository.deployed, &repository.domain) {
+      (true, Some(domain)) => domain,
+      _ => return Ok(()),
+    };
+
+    if is_scheduled(repository.publish_after) {
+      return self.take_offline(repository).await;
+    }
+
+    if tokio::fs::symlink_metadata(self.site_directory(domain))
+      .await
+      .is_ok()
+    {
+      return Ok(());
+    }
+
+    if self.suspension == SuspensionPolicy::Unpublish {
+      let suspended = match self
+        .project_service
+        .get_github_app_uuid(repository.github_app)
+        .await?
+      {
+        Some(github_app) => github_app.suspended_at.is_some() || github_app.removed_at.is_some(),
+        None => true,
+      };
+      if suspended {
+        return Ok(());
+      }
+    }
+
+    if self.activate_latest(repository).await? {
+      info!("Published {}", repository.github_full_name);
+    }
+
+    Ok(())
+  }
+
+  /// Removes the site and its custom domains from the webroot, the releases are kept.
+  async fn take_offline(&self, repository: &repository::Model) -> anyhow::Result<()> {
+    if let Some(domain) = &repository.domain {
+      let link = self.site_directory(domain);
+      match tokio::fs::symlink_metadata(&link).await {
+        Ok(metadata) if metadata.is_symlink() => tokio::fs::remove_file(&link).await?,
+        Ok(_) => warn!(
+          "cannot take {} offline, it is not a link to a release",
+          link.to_str().unwrap_or("~invalid~")
+        ),
+        Err(e) if e.kind() == io::ErrorKind::NotFound => {}
+        Err(e) => return Err(e.into()),
+      }
+    }
+
+    for custom in self.verified_domains(repository.id).await? {
+      self.unlink_custom_domain(&custom.hostname).await?;
+    }
+
+    Ok(())
+  }
+
+  /// Activates the release of the latest successful deployment or rollback together with the custom
+  /// domains, returns false if there is none on disk.
+  async fn activate_latest(&self, repository: &repository::Model) -> anyhow::Result<bool> {
+    let domain = match &repository.domain {
+      Some(value) => value,
+      None => return Ok(false),
+    };
+
+    let release_id = match deployment::Entity::find()
+      .filter(deployment::Column::Repository.eq(repository.id))
+      .filter(deployment::Column::Status.eq(DeploymentStatus::Succeeded))
+      .filter(deployment::Column::Release.is_not_null())
+      .order_by_desc(deployment::Column::FinishedAt)
+      .one(&*self.db)
+      .await?
+      .and_then(|deployment| deployment.release)
+    {
+      Some(value) => value,
+      None => return Ok(false),
+    };
+
+    let site = format!("{}.{}", domain, self.root_domain);
+    let release = self
+      .webroot
+      .join(RELEASE_DIR)
+      .join(&site)
+      .join(release_id.to_string());
+
+    if !tokio::fs::try_exists(&release).await? {
+      warn!("release {} of {} is gone", release_id, site);
+      return Ok(false);
+    }
+
+    self.activate_release(&site, &release).await?;
+    self.link_custom_domains(repository.id, domain).await?;
+
+    Ok(true)
+  }
+
+  /// Applies the suspension policy to the sites of an uninstalled installation. Unpublished sites
+  /// are gone for good since github does not hand out tokens for them anymore. The repositories are
+  /// kept either way and move over with their settings if the app is installed again.
+  pub(crate) async fn remove_sites(
+    &self,
+    repositories: &[repository::Model],
+  ) -> anyhow::Result<()> {
+    if self.suspension == SuspensionPolicy::Freeze {
+      return Ok(());
+    }
+
+    for repository in repositories.iter().filter(|repository| repository.deployed) {
+      self.unpublish(repository).await?;
+    }
+
+    Ok(())
+  }
+
+  /// Runs the given number of deploy loops next to each other.
+  pub(crate) async fn deploy_workers(&self, workers: usize) -> anyhow::Result<()> {
+    try_join_all((0..workers.max(1)).map(|_| self.deploy_loop())).await?;
+
+    Ok(())
   }
 
   pub(crate) async fn deploy_loop(&self) -> anyhow::Result<()> {
     loop {
-      let new_deployment = match self.queue_receiver.lock().await.recv().await {
-        Some(value) => value,
-        None => {
+      let new_deployment = match self.claim_deployment().await {
+        Ok(Some(value)) => value,
+        Ok(None) => {
+          match self.requeue_interrupted().await {
+            Ok(0) => {}
+            Ok(requeued) => info!("Resuming {} interrupted deployments", requeued),
+            Err(e) => error!("cannot requeue interrupted deployments: {e:#}"),
+          }
+
+          // either somebody queues a deployment or another instance did while we were waiting
+          let _ = tokio::time::timeout(POLL_INTERVAL, self.queue_notify.notified()).await;
+          continue;
+        }
+        Err(e) => {
+          error!("cannot fetch queued deployments: {e:#}");
+          tokio::time::sleep(POLL_INTERVAL).await;
           continue;
         }
       };
 
-      let dist = self
-        .webroot
-        .join(format!("{}.{}", new_deployment.domain, self.root_domain));
+      // every deployment runs in its own task, so neither errors nor panics can stop the loop
+      let id = new_deployment.id;
+      let service = self.clone();
+      match tokio::spawn(async move { service.process_deployment(new_deployment).await }).await {
+        Ok(Ok(())) => {}
+        Ok(Err(e)) => error!("deployment {id} failed: {e:#}"),
+        Err(e) => {
+          error!("deployment {id} aborted: {e}");
+
+          let error = anyhow!("deployment aborted unexpectedly");
+          if let Err(e) = self.finish_deployment(id, None, None, Some(&error)).await {
+            error!("cannot record aborted deployment {id}: {e:#}");
+          }
+        }
+      }
+    }
+  }
+
+  /// Marks the oldest queued deployment as running, unless its repository or domain is already being
+  /// deployed. Claims are serialized by an advisory lock, so two loops cannot pick the same site.
+  async fn claim_deployment(&self) -> anyhow::Result<Option<deployment::Model>> {
+    let txn = self.db.begin().await?;
+
+    txn
+      .execute(Statement::from_sql_and_values(
+        DbBackend::Postgres,
+        "SELECT pg_advisory_xact_lock($1)",
+        [CLAIM_LOCK.into()],
+      ))
+      .await?;
+
+    let claimed = deployment::Entity::find()
+      .from_raw_sql(Statement::from_string(
+        DbBackend::Postgres,
+        r#"
+        UPDATE deployment SET status = 'running', started_at = now()
+        WHERE id = (
+          SELECT queued.id FROM deployment queued
+          JOIN repository site ON site.id = queued.repository
+          WHERE queued.status = 'queued' AND NOT EXISTS (
+            SELECT 1 FROM deployment running
+            JOIN repository running_site ON running_site.id = running.repository
+            WHERE running.status = 'running'
+              AND (running.repository = queued.repository OR running_site.domain = site.domain)
+          )
+          ORDER BY queued.created_at
+          LIMIT 1
+          FOR UPDATE OF queued SKIP LOCKED
+        )
+        RETURNING *
+        "#,
+      ))
+      .one(&txn)
+      .await?;
+
+    txn.commit().await?;
+
+    Ok(claimed)
+  }
+
+  /// Runs a single deployment, retrying transient failures, and records its outcome.
+  async fn process_deployment(&self, new_deployment: deployment::Model) -> anyhow::Result<()> {
+    let id = new_deployment.id;
+
+    let attempts = async {
+      let mut attempt = 1;
+      let mut backoff = RETRY_BACKOFF;
+      loop {
+        let result = match self.prepare_deployment(&new_deployment).await {
+          Ok(job) => self.run_deployment(&job).await.map(|size| (job, size)),
+          Err(e) => Err(e),
+        };
+
+        match result {
+          Err(e) if attempt < MAX_ATTEMPTS && is_transient(&e) => {
+            warn!(
+              "attempt {attempt} of deployment {id} failed, retrying in {}s: {e:#}",
+              backoff.as_secs()
+            );
+            tokio::time::sleep(backoff).await;
+            backoff *= 2;
+            attempt += 1;
+          }
+          result => break result,
+        }
+      }
+    };
+
+    // bounded, so that the lease of a running deployment never runs out while it is still alive
+    let result = tokio::time::timeout(DEPLOYMENT_TIMEOUT, attempts)
+      .await
+      .unwrap_or_else(|_| {
+        Err(anyhow!(
+          "deployment did not finish within {}s",
+          DEPLOYMENT_TIMEOUT.as_secs()
+        ))
+      });
+
+    let size = result.as_ref().ok().map(|(_, size)| *size);
+    let anonymized = result.as_ref().ok().map(|(job, _)| !job.camera_ready);
+    self
+      .finish_deployment(id, size, anonymized, result.as_ref().err())
+      .await?;
+    let (job, _) = result?;
+
+    let site = format!("{}.{}", job.domain, self.root_domain);
+    self
+      .link_custom_domains(job.repository, &job.domain)
+      .await?;
+    self.prune_releases(job.repository, &site).await?;
+    self.update_disk_usage(job.repository).await
+  }
+
+  /// Looks up where the deployment goes and fetches a fresh token for downloading it.
+  async fn prepare_deployment(
+    &self,
+    new_deployment: &deployment::Model,
+  ) -> anyhow::Result<DeploymentJob> {
+    let repository = self
+      .project_service
+      .get_repository_uuid(new_deployment.repository)
+      .await?
+      .ok_or_else(|| anyhow!("repository {} does not exist", new_deployment.repository))?;
+
+    let domain = repository
+      .domain
+      .ok_or_else(|| anyhow!("no domain configured for {}", repository.github_full_name))?;
+
+    let github_app = self
+      .project_service
+      .get_github_app_uuid(repository.github_app)
+      .await?
+      .ok_or_else(|| anyhow!("github app {} does not exist", repository.github_app))?;
+
+    if github_app.suspended_at.is_some() || github_app.removed_at.is_some() {
+      return Err(anyhow!(
+        "installation {} is suspended or uninstalled",
+        github_app.installation_id
+      ));
+    }
+
+    let redactions = self.project_service.redaction_terms(repository.id).await?;
+
+    let access_token = self
+      .token_service
+      .fetch_access_tokens_repo(
+        github_app.installation_id,
+        vec![repository.github_short_name],
+      )
+      .await?;
+
+    Ok(DeploymentJob {
+      id: new_deployment.id,
+      repository: repository.id,
+      full_name: repository.github_full_name,
+      token: access_token.token,
+      commit_id: new_deployment.commit_id.clone(),
+      domain,
+      path: repository.path.map(PathBuf::from),
+      quota: self.quota(&github_app),
+      redactions,
+      camera_ready: repository.camera_ready,
+      link: repository.camera_ready_link,
+      scheduled: is_scheduled(repository.publish_after),
+    })
+  }
+
+  async fn finish_deployment(
+    &self,
+    id: Uuid,
+    size: Option<u64>,
+    anonymized: Option<bool>,
+    error: Option<&anyhow::Error>,
+  ) -> anyhow::Result<()> {
+    deployment::ActiveModel {
+      id: Unchanged(id),
+      release: Set(error.is_none().then_some(id)),
+      retained: Set(error.is_none()),
+      size: Set(size.map(|size| size as i64)),
+      anonymized: Set(anonymized),
+      status: Set(match error {
+        None => DeploymentStatus::Succeeded,
+        Some(_) => DeploymentStatus::Failed,
+      }),
+      error: Set(error.map(|e| format!("{e:#}"))),
+      finished_at: Set(Some(OffsetDateTime::now_utc())),
+      ..Default::default()
+    }
+    .update(&*self.db)
+    .await?;
+
+    Ok(())
+  }
+
+  /// Downloads the commit into a new release named after the deployment and activates it unless the
+  /// site waits for its publish date, returns the size of the release.
+  async fn run_deployment(&self, new_deployment: &DeploymentJob) -> anyhow::Result<u64> {
+    let site = format!("{}.{}", new_deployment.domain, self.root_domain);
+    let staging = self
+      .webroot
+      .join(STAGING_DIR)
+      .join(new_deployment.id.to_string());
+    let release = self
+      .webroot
+      .join(RELEASE_DIR)
+      .join(&site)
+      .join(new_deployment.id.to_string());
+
+    info!(
+      "Deploying {}#{} into {}",
+      new_deployment.full_name,
+      new_deployment.commit_id,
+      staging.to_str().unwrap_or("~invalid~")
+    );
+
+    tokio::fs::create_dir_all(&staging).await?;
+
+    let result = async {
+      let root = match (new_deployment.camera_ready, &new_deployment.link) {
+        (true, Some(CameraReadyLink::Redirect)) => {
+          write_redirect(&staging, &new_deployment.full_name).await?;
+          staging.clone()
+        }
+        _ => self.assemble_release(new_deployment, &staging).await?,
+      };
+
+      let size = tree_size(&root).await?;
+      self.check_quota(new_deployment, size).await?;
+
+      tokio::fs::create_dir_all(release.parent().unwrap_or(&self.webroot)).await?;
+      tokio::fs::rename(&root, &release).await?;
+
+      if new_deployment.scheduled {
+        info!("Keeping {} offline until its publish date", site);
+      } else {
+        self.activate_release(&site, &release).await?;
+      }
+      Ok(size)
+    }
+    .await;
+
+    // leftovers would make a retry of this deployment fail
+    remove_if_exists(&staging).await;
+    if result.is_err() {
+      remove_if_exists(&release).await;
+    }
+
+    result
+  }
 
-      if tokio::fs::try_exists(&dist).await? {
-        info!("Cleaning existing {}", dist.to_str().unwrap_or("~invalid~"));
-        tokio::fs::remove_dir_all(&dist).await?;
+  /// Unpacks and anonymizes the commit inside of `staging`, returns the folder which becomes
+  /// the release.
+  async fn assemble_release(
+    &self,
+    new_deployment: &DeploymentJob,
+    staging: &Path,
+  ) -> anyhow::Result<PathBuf> {
+    self
+      .unpack_release(new_deployment, staging, new_deployment.path.as_deref())
+      .await?;
+
+    let config = RepositoryConfig::load(staging).await?;
+
+    // the config file wins over the folder chosen when the site was set up
+    let published = config.path.as_deref().or(new_deployment.path.as_deref());
+
+    // another folder in the config file needs more than what was unpacked
+    if let (Some(unpacked), Some(published)) = (new_deployment.path.as_deref(), published) {
+      if !published.starts_with(unpacked) {
+        info!(
+          "Unpacking {} again as requested by its config file",
+          new_deployment.full_name
+        );
+        tokio::fs::remove_dir_all(staging).await?;
+        tokio::fs::create_dir_all(staging).await?;
+        self
+          .unpack_release(new_deployment, staging, Some(published))
+          .await?;
       }
+    }
+
+    let root = site_root(staging, published).await?;
 
+    // the default excludes only exist to anonymize the site
+    let exclude = match new_deployment.camera_ready {
+      true => &config.camera_ready_exclude,
+      false => &config.exclude,
+    };
+    for path in remove_excluded(&root, exclude).await? {
       info!(
-        "Deploying {}#{} into {}",
-        new_deployment.full_name,
-        new_deployment.commit_id,
-        dist.to_str().unwrap_or("~invalid~")
+        "Excluded {} of {}",
+        path.to_str().unwrap_or("~invalid~"),
+        new_deployment.full_name
       );
+    }
 
-      let url = format!(
-        "https://github.com/{}/tarball/{}",
-        new_deployment.full_name, new_deployment.commit_id
+    // broken rules fail the deployment instead of being ignored silently while serving
+    let redirects = RedirectRules::load(&root).await?;
+    if !redirects.is_empty() {
+      info!(
+        "Found {} redirect rules in {}",
+        redirects.len(),
+        new_deployment.full_name
       );
+    }
 
-      let stream = self
-        .client
-        .get(url)
-        .bearer_auth(new_deployment.token)
-        .send()
-        .await?
-        .error_for_status()?
-        .bytes_stream()
-        .map(|x| Ok::<_, io::Error>(x.unwrap()));
+    if new_deployment.camera_ready {
+      if new_deployment.link == Some(CameraReadyLink::Banner) {
+        let pages = insert_banner(&root, &new_deployment.full_name).await?;
+        info!(
+          "Added a banner to {pages} pages of {}",
+          new_deployment.full_name
+        );
+      }
+
+      return Ok(root);
+    }
+
+    let mut terms = new_deployment.redactions.clone();
+    terms.extend(config.redact);
+    self
+      .anonymize_release(new_deployment, &root, &terms)
+      .await?;
+    self.scrub_release(new_deployment, &root).await?;
+
+    Ok(root)
+  }
 
-      let reader = StreamReader::new(stream);
-      let decoder = GzipDecoder::new(reader);
-      let mut archive = Archive::new(decoder);
+  /// Re-activates the most recent retained release of `commit_id` which was built for the current
+  /// camera ready mode of the repository, an anonymized release never replaces a camera ready site
+  /// and the other way round.
+  pub(crate) async fn rollback(
+    &self,
+    repository: &repository::Model,
+    domain: &str,
+    commit_id: &str,
+  ) -> anyhow::Result<Rollback> {
+    let site = format!("{}.{}", domain, self.root_domain);
 
-      archive.unpack(&dist).await?;
+    let releases = deployment::Entity::find()
+      .filter(deployment::Column::Repository.eq(repository.id))
+      .filter(deployment::Column::CommitId.eq(commit_id))
+      .filter(deployment::Column::Retained.eq(true))
+      .order_by_desc(deployment::Column::CreatedAt)
+      .all(&*self.db)
+      .await?;
 
-      // fixing stupid extra folder generated by github
-      let mut dir = tokio::fs::read_dir(&dist).await?;
-      let entry = dir.next_entry().await?.unwrap();
-      let entry = entry.path();
+    let deployment = match rollback_release(releases, repository.camera_ready) {
+      Ok(value) => value,
+      Err(rollback) => return Ok(rollback),
+    };
+
+    let release_id = deployment.release.unwrap_or(deployment.id);
+    let release = self
+      .webroot
+      .join(RELEASE_DIR)
+      .join(&site)
+      .join(release_id.to_string());
+
+    if !tokio::fs::try_exists(&release).await? {
+      return Ok(Rollback::Missing);
+    }
+
+    info!("Rolling back {} to {}", site, commit_id);
+
+    let started_at = OffsetDateTime::now_utc();
+    // a site waiting for its publish date gets the release of the latest rollback once it is reached
+    let result = match is_scheduled(repository.publish_after) {
+      true => Ok(()),
+      false => match self.activate_release(&site, &release).await {
+        Ok(()) => self.link_custom_domains(repository.id, domain).await,
+        Err(e) => Err(e),
+      },
+    };
+
+    deployment::ActiveModel {
+      id: Set(Uuid::new_v4()),
+      repository: Set(repository.id),
+      commit_id: Set(commit_id.to_string()),
+      status: Set(match &result {
+        Ok(()) => DeploymentStatus::Succeeded,
+        Err(_) => DeploymentStatus::Failed,
+      }),
+      trigger: Set(DeploymentTrigger::Rollback),
+      release: Set(Some(release_id)),
+      retained: Set(false),
+      error: Set(result.as_ref().err().map(|e| format!("{e:#}"))),
+      size: Set(None),
+      anonymized: Set(deployment.anonymized),
+      created_at: Set(started_at),
+      started_at: Set(Some(started_at)),
+      finished_at: Set(Some(OffsetDateTime::now_utc())),
+    }
+    .insert(&*self.db)
+    .await?;
+
+    result.map(|_| Rollback::Activated)
+  }
+
+  /// Replaces the identity strings configured for the repository and records where they were found.
+  async fn anonymize_release(
+    &self,
+    new_deployment: &DeploymentJob,
+    root: &Path,
+    terms: &[String],
+  ) -> anyhow::Result<()> {
+    let redactor = match Redactor::new(terms)? {
+      Some(value) => value,
+      None => return Ok(()),
+    };
+
+    let report = redact_tree(root, &redactor).await?;
+
+    for (path, replacements) in &report {
+      info!(
+        "Redacted {} occurrences in {} of {}",
+        replacements,
+        path.to_str().unwrap_or("~invalid~"),
+        new_deployment.full_name
+      );
+    }
+
+    // a retried deployment must not report its files twice
+    redaction_report::Entity::delete_many()
+      .filter(redaction_report::Column::Deployment.eq(new_deployment.id))
+      .exec(&*self.db)
+      .await?;
+
+    if !report.is_empty() {
+      redaction_report::Entity::insert_many(report.into_iter().map(|(path, replacements)| {
+        redaction_report::ActiveModel {
+          id: Set(Uuid::new_v4()),
+          deployment: Set(new_deployment.id),
+          path: Set(path.to_string_lossy().into_owned()),
+          replacements: Set(replacements as i32),
+        }
+      }))
+      .exec(&*self.db)
+      .await?;
+    }
 
-      let mut child = tokio::fs::read_dir(&entry).await?;
-      while let Some(path) = child.next_entry().await? {
-        tokio::fs::rename(path.path(), dist.join(path.file_name())).await?;
+    Ok(())
+  }
+
+  /// Strips metadata naming the authors from images, pdf and office files.
+  async fn scrub_release(&self, new_deployment: &DeploymentJob, root: &Path) -> anyhow::Result<()> {
+    let root = root.to_path_buf();
+    let report = tokio::task::spawn_blocking(move || scrub_tree(&root)).await??;
+
+    for (path, removed) in report.scrubbed {
+      info!(
+        "Removed {} from {} of {}",
+        removed.join(", "),
+        path.to_str().unwrap_or("~invalid~"),
+        new_deployment.full_name
+      );
+    }
+
+    for path in report.skipped {
+      warn!(
+        "Left {} of {} unchanged, its content does not match its name",
+        path.to_str().unwrap_or("~invalid~"),
+        new_deployment.full_name
+      );
+    }
+
+    Ok(())
+  }
+
+  /// Fails if the new release together with the releases surviving the next prune exceeds the quota.
+  async fn check_quota(&self, new_deployment: &DeploymentJob, size: u64) -> anyhow::Result<()> {
+    let kept: u64 = deployment::Entity::find()
+      .filter(deployment::Column::Repository.eq(new_deployment.repository))
+      .filter(deployment::Column::Retained.eq(true))
+      .order_by_desc(deployment::Column::CreatedAt)
+      .limit(self.history.saturating_sub(1))
+      .all(&*self.db)
+      .await?
+      .iter()
+      .filter_map(|deployment| deployment.size)
+      .map(|size| size.max(0) as u64)
+      .sum();
+
+    if size + kept > new_deployment.quota {
+      return Err(anyhow!(
+        "release of {} bytes exceeds the quota of {} bytes, {} bytes are used by previous releases",
+        size,
+        new_deployment.quota,
+        kept
+      ));
+    }
+
+    Ok(())
+  }
+
+  /// Stores the size of all releases of the repository which are still on disk.
+  async fn update_disk_usage(&self, repository: Uuid) -> anyhow::Result<()> {
+    let usage: i64 = deployment::Entity::find()
+      .filter(deployment::Column::Repository.eq(repository))
+      .filter(deployment::Column::Retained.eq(true))
+      .all(&*self.db)
+      .await?
+      .iter()
+      .filter_map(|deployment| deployment.size)
+      .sum();
+
+    repository::ActiveModel {
+      id: Unchanged(repository),
+      disk_usage: Set(usage),
+      ..Default::default()
+    }
+    .update(&*self.db)
+    .await?;
+
+    Ok(())
+  }
+
+  /// Removes every release of the repository exceeding the configured history, except the active one.
+  async fn prune_releases(&self, repository: Uuid, site: &str) -> anyhow::Result<()> {
+    let link = self.webroot.join(site);
+    let active = match tokio::fs::read_link(&link).await {
+      Ok(target) => Some(target),
+      // a site waiting for its publish date has no active release yet, the latest one becomes it
+      Err(e) if e.kind() == io::ErrorKind::NotFound => None,
+      Err(e) => return Err(e.into()),
+    };
+
+    let expired = deployment::Entity::find()
+      .filter(deployment::Column::Repository.eq(repository))
+      .filter(deployment::Column::Retained.eq(true))
+      .order_by_desc(deployment::Column::CreatedAt)
+      .offset(match active {
+        Some(_) => self.history,
+        None => self.history.max(1),
+      })
+      .all(&*self.db)
+      .await?;
+
+    for deployment in expired {
+      if active
+        .as_ref()
+        .is_some_and(|active| active.ends_with(deployment.id.to_string()))
+      {
+        continue;
+      }
+
+      let release = self
+        .webroot
+        .join(RELEASE_DIR)
+        .join(site)
+        .join(deployment.id.to_string());
+
+      info!(
+        "Pruning release {}",
+        release.to_str().unwrap_or("~invalid~")
+      );
+
+      match tokio::fs::remove_dir_all(&release).await {
+        Ok(()) => {}
+        Err(e) if e.kind() == io::ErrorKind::NotFound => {}
+        Err(e) => return Err(e.into()),
       }
 
-      tokio::fs::remove_dir(entry).await?;
+      let mut deployment: deployment::ActiveModel = deployment.into();
+      deployment.retained = Set(false);
+      deployment.update(&*self.db).await?;
     }
+
+    Ok(())
   }
+
+  /// Streams the tarball of the given commit into `staging`, only `subtree` and the config file are
+  /// unpacked if given.
+  async fn unpack_release(
+    &self,
+    deployment: &DeploymentJob,
+    staging: &Path,
+    subtree: Option<&Path>,
+  ) -> anyhow::Result<()> {
+    let url = format!(
+      "https://github.com/{}/tarball/{}",
+      deployment.full_name, deployment.commit_id
+    );
+
+    let stream = self
+      .client
+      .get(url)
+      .bearer_auth(&deployment.token)
+      .send()
+      .await?
+      .error_for_status()?
+      .bytes_stream()
+      .map(|x| x.map_err(io::Error::other));
+
+    let reader = StreamReader::new(stream);
+    let decoder = GzipDecoder::new(reader);
+    let mut archive = Archive::new(decoder);
+
+    let size = extract_archive(&mut archive, staging, subtree, &self.limits)
+      .await
+      .map_err(|e| e.context(format!("cannot unpack {}", deployment.full_name)))?;
+
+    info!("Unpacked {} bytes of {}", size, deployment.full_name);
+
+    Ok(())
+  }
+
+  async fn verified_domains(&self, repository: Uuid) -> anyhow::Result<Vec<custom_domain::Model>> {
+    Ok(
+      custom_domain::Entity::find()
+        .filter(custom_domain::Column::Repository.eq(repository))
+        .filter(custom_domain::Column::VerifiedAt.is_not_null())
+        .all(&*self.db)
+        .await?,
+    )
+  }
+
+  /// Points `<webroot>/<hostname>` of every verified custom domain of the repository to the site, so
+  /// a web server serving `<webroot>/$host` picks them up as well.
+  pub(crate) async fn link_custom_domains(
+    &self,
+    repository: Uuid,
+    domain: &str,
+  ) -> anyhow::Result<()> {
+    let site = format!("{}.{}", domain, self.root_domain);
+
+    // sites which are offline, for example until their publish date, get linked once they are online
+    if tokio::fs::symlink_metadata(self.webroot.join(&site))
+      .await
+      .is_err()
+    {
+      return Ok(());
+    }
+
+    for custom in self.verified_domains(repository).await? {
+      let link = self.webroot.join(&custom.hostname);
+      let temporary_link = self
+        .webroot
+        .join(format!(".{}.{}", custom.hostname, Uuid::new_v4()));
+
+      // relative to the site link, so later releases and rollbacks need no update
+      tokio::fs::symlink(&site, &temporary_link).await?;
+      tokio::fs::rename(&temporary_link, &link).await?;
+      info!("Linked {} to {}", custom.hostname, site);
+    }
+
+    Ok(())
+  }
+
+  /// Stops serving the site under a custom domain, the site itself stays.
+  pub(crate) async fn unlink_custom_domain(&self, hostname: &str) -> anyhow::Result<()> {
+    let link = self.webroot.join(hostname);
+
+    match tokio::fs::symlink_metadata(&link).await {
+      Ok(metadata) if metadata.is_symlink() => tokio::fs::remove_file(&link).await?,
+      Ok(_) => return Err(anyhow!("{hostname} is not a link to a site")),
+      Err(e) if e.kind() == io::ErrorKind::NotFound => {}
+      Err(e) => return Err(e.into()),
+    }
+
+    info!("Unlinked {}", hostname);
+    Ok(())
+  }
+
+  /// Atomically points `<webroot>/<site>` to `release`, previous releases are kept for rollbacks.
+  async fn activate_release(&self, site: &str, release: &Path) -> anyhow::Result<()> {
+    let link = self.webroot.join(site);
+    let target = release.strip_prefix(&self.webroot)?;
+
+    let legacy = match tokio::fs::symlink_metadata(&link).await {
+      Ok(metadata) if metadata.is_symlink() => None,
+      Ok(_) => {
+        // sites deployed before releases existed are plain directories, which cannot be replaced by
+        // a symlink in one step, so they are moved into the release folder first
+        let legacy = self
+          .webroot
+          .join(RELEASE_DIR)
+          .join(site)
+          .join(Uuid::new_v4().to_string());
+        tokio::fs::rename(&link, &legacy).await?;
+        Some(legacy)
+      }
+      Err(e) if e.kind() == io::ErrorKind::NotFound => None,
+      Err(e) => return Err(e.into()),
+    };
+
+    // rename(2) replaces the old symlink atomically, so the site is never missing
+    let temporary_link = self.webroot.join(format!(".{}.{}", site, Uuid::new_v4()));
+    tokio::fs::symlink(target, &temporary_link).await?;
+    tokio::fs::rename(&temporary_link, &link).await?;
+
+    info!(
+      "Activated {} for {}",
+      target.to_str().unwrap_or("~invalid~"),
+      site
+    );
+
+    // legacy sites have no deployment record, so they would never be pruned
+    if let Some(legacy) = legacy {
+      info!("Cleaning legacy {}", legacy.to_str().unwrap_or("~invalid~"));
+      remove_if_exists(&legacy).await;
+    }
+
+    Ok(())
+  }
+}
+
+/// Removes a directory which is no longer needed, failing to do so is not worth failing a deployment.
+async fn remove_if_exists(path: &Path) {
+  match tokio::fs::remove_dir_all(path).await {
+    Ok(()) => {}
+    Err(e) if e.kind() == io::ErrorKind::NotFound => {}
+    Err(e) => warn!(
+      "cannot remove {}: {e}",
+      path.to_str().unwrap_or("~invalid~")
+    ),
+  }
+}
+
+/// Sums up the size of every file below `root`, symlinks are not followed and hardlinked files are
+/// only counted once.
+async fn tree_size(root: &Path) -> io::Result<u64> {
+  let mut size = 0;
+  let mut directories = vec![root.to_path_buf()];
+  let mut linked = HashSet::new();
+
+  while let Some(directory) = directories.pop() {
+    let mut entries = tokio::fs::read_dir(&directory).await?;
+
+    while let Some(entry) = entries.next_entry().await? {
+      let metadata = entry.metadata().await?;
+      if metadata.is_dir() {
+        directories.push(entry.path());
+      } else if metadata.is_file()
+        && (metadata.nlink() == 1 || linked.insert((metadata.dev(), metadata.ino())))
+      {
+        size += metadata.len();
+      }
+    }
+  }
+
+  Ok(size)
+}
+
+/// Whether the site has to stay offline until its publish date.
+fn is_scheduled(publish_after: Option<OffsetDateTime>) -> bool {
+  publish_after.is_some_and(|publish_after| publish_after > OffsetDateTime::now_utc())
+}
+
+/// Picks the most recent of the retained `releases` of a commit which matches the camera ready mode.
+fn rollback_release(
+  releases: Vec<deployment::Model>,
+  camera_ready: bool,
+) -> Result<deployment::Model, Rollback> {
+  if releases.is_empty() {
+    return Err(Rollback::Missing);
+  }
+
+  releases
+    .into_iter()
+    .find(|release| release.anonymized == Some(!camera_ready))
+    .ok_or(Rollback::Mismatched)
+}
+
+/// Network hiccups and server side errors from github are worth another try, everything else is not.
+fn is_transient(error: &anyhow::Error) -> bool {
+  fn is_transient_request(error: &reqwest::Error) -> bool {
+    error.is_timeout()
+      || error.is_connect()
+      || error.is_body()
+      || error
+        .status()
+        .is_some_and(|status| status.is_server_error() || status == StatusCode::TOO_MANY_REQUESTS)
+  }
+
+  error.chain().any(|cause| {
+    if let Some(error) = cause.downcast_ref::<reqwest::Error>() {
+      return is_transient_request(error);
+    }
+
+    if let Some(error) = cause.downcast_ref::<io::Error>() {
+      // errors of the tarball stream are wrapped into io errors
+      return match error
+        .get_ref()
+        .and_then(|inner| inner.downcast_ref::<reqwest::Error>())
+      {
+        Some(error) => is_transient_request(error),
+        None => matches!(
+          error.kind(),
+          io::ErrorKind::TimedOut
+            | io::ErrorKind::ConnectionReset
+            | io::ErrorKind::ConnectionAborted
+            | io::ErrorKind::UnexpectedEof
+        ),
+      };
+    }
+
+    false
+  })
 }
 
 #[cfg(test)]
 mod tests {
-  use std::path::PathBuf;
+  use std::path::{Path, PathBuf};
+  use std::sync::Arc;
+
+  use entity::deployment::{self, DeploymentStatus, DeploymentTrigger};
+  use sea_orm::DatabaseConnection;
+  use time::OffsetDateTime;
+  use uuid::Uuid;
 
-  use crate::service::deploy::DeploymentService;
+  use crate::service::deploy::{
+    rollback_release, tree_size, DeploymentService, Rollback, SuspensionPolicy, RELEASE_DIR,
+  };
+  use crate::service::extract::ExtractionLimits;
+  use crate::service::github_app::ProjectService;
+  use crate::service::token::TokenService;
+
+  fn service(webroot: &Path) -> DeploymentService {
+    let db = Arc::new(DatabaseConnection::Disconnected);
+
+    DeploymentService::new(
+      db.clone(),
+      ProjectService::from_db(db),
+      TokenService::new(String::new(), Path::new("/dev/null")),
+      webroot.to_path_buf(),
+      "m4rc3l.de".to_string(),
+      5,
+      ExtractionLimits {
+        max_total_size: 1 << 30,
+        max_files: 100_000,
+        max_file_size: 1 << 27,
+      },
+      1 << 31,
+      SuspensionPolicy::Unpublish,
+    )
+  }
+
+  async fn release(webroot: &Path, site: &str, id: &str, content: &str) -> PathBuf {
+    let release = webroot.join(RELEASE_DIR).join(site).join(id);
+    tokio::fs::create_dir_all(&release).await.unwrap();
+    tokio::fs::write(release.join("index.html"), content)
+      .await
+      .unwrap();
+    release
+  }
 
   #[tokio::test]
-  async fn test_deployment_service() -> anyhow::Result<()> {
-    let service = DeploymentService::new(PathBuf::from("."), "m4rc3l.de".to_string());
+  async fn test_activates_releases() {
+    let webroot = tempfile::tempdir().unwrap();
+    let service = service(webroot.path());
+    let site = "paper.m4rc3l.de";
+
+    // a site deployed before releases existed
+    let legacy = webroot.path().join(site);
+    tokio::fs::create_dir_all(&legacy).await.unwrap();
+    tokio::fs::write(legacy.join("index.html"), "legacy")
+      .await
+      .unwrap();
+
+    let first = release(webroot.path(), site, "first", "first").await;
+    service.activate_release(site, &first).await.unwrap();
+
+    assert_eq!(
+      tokio::fs::read_link(&legacy).await.unwrap(),
+      Path::new(RELEASE_DIR).join(site).join("first")
+    );
+    assert_eq!(
+      tokio::fs::read_to_string(legacy.join("index.html"))
+        .await
+        .unwrap(),
+      "first"
+    );
+    // the legacy directory is cleaned up instead of lingering as an unknown release
+    assert_eq!(
+      std::fs::read_dir(webroot.path().join(RELEASE_DIR).join(site))
+        .unwrap()
+        .count(),
+      1
+    );
+
+    let second = release(webroot.path(), site, "second", "second").await;
+    service.activate_release(site, &second).await.unwrap();
+
+    assert_eq!(
+      tokio::fs::read_to_string(legacy.join("index.html"))
+        .await
+        .unwrap(),
+      "second"
+    );
+    assert!(first.exists());
+    // no temporary links are left behind
+    assert_eq!(std::fs::read_dir(webroot.path()).unwrap().count(), 2);
+  }
+
+  fn deployment(anonymized: Option<bool>) -> deployment::Model {
+    deployment::Model {
+      id: Uuid::new_v4(),
+      repository: Uuid::new_v4(),
+      commit_id: "abc".to_string(),
+      status: DeploymentStatus::Succeeded,
+      trigger: DeploymentTrigger::Webhook,
+      release: None,
+      retained: true,
+      error: None,
+      size: Some(1),
+      anonymized,
+      created_at: OffsetDateTime::now_utc(),
+      started_at: None,
+      finished_at: None,
+    }
+  }
+
+  #[test]
+  fn test_rolls_back_to_same_mode() {
+    let anonymized = deployment(Some(true));
+    let camera_ready = deployment(Some(false));
+    let releases = vec![camera_ready.clone(), anonymized.clone()];
+
+    assert_eq!(rollback_release(releases.clone(), false), Ok(anonymized));
+    assert_eq!(rollback_release(releases, true), Ok(camera_ready));
+
+    assert_eq!(
+      rollback_release(vec![deployment(Some(true))], true),
+      Err(Rollback::Mismatched)
+    );
+    // releases from before the mode was recorded are never activated
+    assert_eq!(
+      rollback_release(vec![deployment(None)], false),
+      Err(Rollback::Mismatched)
+    );
+    assert_eq!(rollback_release(vec![], false), Err(Rollback::Missing));
+  }
 
-    service
-      .deploy("MarcelCoding/zia", "abc", "main", "zia".to_string())
+  #[tokio::test]
+  async fn test_counts_hardlinks_once() {
+    let root = tempfile::tempdir().unwrap();
+    tokio::fs::create_dir_all(root.path().join("docs"))
+      .await
+      .unwrap();
+    tokio::fs::write(root.path().join("data.bin"), [0u8; 100])
+      .await
+      .unwrap();
+    tokio::fs::write(root.path().join("docs/index.html"), [0u8; 10])
       .await
+      .unwrap();
+    tokio::fs::hard_link(
+      root.path().join("data.bin"),
+      root.path().join("docs/data.bin"),
+    )
+    .await
+    .unwrap();
+    tokio::fs::symlink("../data.bin", root.path().join("docs/link.bin"))
+      .await
+      .unwrap();
+
+    assert_eq!(tree_size(root.path()).await.unwrap(), 110);
   }
 }
@@ -0,0 +1,137 @@
+use anyhow::anyhow;
+use uuid::Uuid;
+
+/// longest label dns allows
+const MAX_LABEL_LENGTH: usize = 63;
+/// longest name dns allows, without the trailing dot
+const MAX_HOSTNAME_LENGTH: usize = 253;
+/// hex digits of a random site name, 64 bit are not guessable
+const RANDOM_NAME_LENGTH: usize = 16;
+/// subdomains used by the service itself or commonly expected to belong to the operator
+pub(crate) const RESERVED_NAMES: [&str; 12] = [
+  "api", "www", "admin", "app", "auth", "cdn", "docs", "mail", "static", "status", "ftp", "ns",
+];
+
+/// Generates a site name which does not reveal anything about the repository.
+pub(crate) fn random_domain() -> String {
+  let mut name = Uuid::new_v4().simple().to_string();
+  name.truncate(RANDOM_NAME_LENGTH);
+  name
+}
+
+/// Checks a site name chosen by an author against the dns label rules and the reserved names,
+/// returns it lowercased.
+pub(crate) fn validate_domain(domain: &str) -> anyhow::Result<String> {
+  let domain = domain.trim().to_lowercase();
+
+  check_label(&domain)?;
+
+  if RESERVED_NAMES.contains(&domain.as_str()) {
+    return Err(anyhow!("{domain:?} is reserved"));
+  }
+
+  Ok(domain)
+}
+
+/// Checks a hostname outside of the root domain which should serve a site, returns it lowercased
+/// and without a trailing dot.
+pub(crate) fn validate_hostname(hostname: &str, root_domain: &str) -> anyhow::Result<String> {
+  let hostname = hostname.trim().trim_end_matches('.').to_lowercase();
+  let root_domain = root_domain.to_lowercase();
+
+  if hostname.len() > MAX_HOSTNAME_LENGTH {
+    return Err(anyhow!(
+      "{hostname:?} is longer than {MAX_HOSTNAME_LENGTH} characters"
+    ));
+  }
+
+  let labels = hostname.split('.').collect::<Vec<&str>>();
+  if labels.len() < 2 {
+    return Err(anyhow!("{hostname:?} is not a fully qualified hostname"));
+  }
+  for label in &labels {
+    check_label(label)?;
+  }
+  if labels
+    .last()
+    .is_some_and(|tld| tld.chars().all(|c| c.is_ascii_digit()))
+  {
+    return Err(anyhow!("{hostname:?} is an ip address"));
+  }
+
+  if hostname == root_domain || hostname.ends_with(&format!(".{root_domain}")) {
+    return Err(anyhow!("{hostname:?} already belongs to this service"));
+  }
+
+  Ok(hostname)
+}
+
+/// Single label of a hostname, which has to be lowercased already.
+fn check_label(label: &str) -> anyhow::Result<()> {
+  if label.is_empty() || label.len() > MAX_LABEL_LENGTH {
+    return Err(anyhow!(
+      "{label:?} has to be between 1 and {MAX_LABEL_LENGTH} characters long"
+    ));
+  }
+
+  if !label
+    .chars()
+    .all(|c| c.is_ascii_lowercase() || c.is_ascii_digit() || c == '-')
+  {
+    return Err(anyhow!(
+      "{label:?} may only contain letters, digits and hyphens"
+    ));
+  }
+
+  if label.starts_with('-') || label.ends_with('-') {
+    return Err(anyhow!("{label:?} may not start or end with a hyphen"));
+  }
+
+  Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+  use crate::service::domain::{random_domain, validate_domain, validate_hostname};
+
+  #[test]
+  fn test_validates_domain() {
+    assert_eq!(validate_domain(" Paper-2024 ").unwrap(), "paper-2024");
+    assert_eq!(validate_domain(&"a".repeat(63)).unwrap(), "a".repeat(63));
+
+    assert!(validate_domain("").is_err());
+    assert!(validate_domain(&"a".repeat(64)).is_err());
+    assert!(validate_domain("paper.example").is_err());
+    assert!(validate_domain("../paper").is_err());
+    assert!(validate_domain("-paper").is_err());
+    assert!(validate_domain("paper-").is_err());
+    assert!(validate_domain("WWW").is_err());
+  }
+
+  #[test]
+  fn test_validates_hostname() {
+    let root = "doubleblind.science";
+
+    assert_eq!(
+      validate_hostname(" Artifact.Conference-Track.org. ", root).unwrap(),
+      "artifact.conference-track.org"
+    );
+
+    assert!(validate_hostname("localhost", root).is_err());
+    assert!(validate_hostname("paper.doubleblind.science", root).is_err());
+    assert!(validate_hostname("DoubleBlind.Science", root).is_err());
+    assert!(validate_hostname("127.0.0.1", root).is_err());
+    assert!(validate_hostname("a..example.org", root).is_err());
+    assert!(validate_hostname("../example.org", root).is_err());
+    assert!(validate_hostname(&format!("{}.org", "a.".repeat(130)), root).is_err());
+  }
+
+  #[test]
+  fn test_generates_valid_domain() {
+    let domain = random_domain();
+
+    assert_eq!(domain.len(), 16);
+    assert_eq!(validate_domain(&domain).unwrap(), domain);
+    assert_ne!(domain, random_domain());
+  }
+}
@@ -0,0 +1,257 @@
+use std::path::{Path, PathBuf};
+
+use anyhow::anyhow;
+use regex::Regex;
+
+use crate::service::config::CONFIG_FILE;
+
+/// git and ci metadata which routinely names the authors, excluded unless a repository keeps it
+pub(crate) const DEFAULT_EXCLUDES: [&str; 7] = [
+  ".github/",
+  ".gitmodules",
+  "CODEOWNERS",
+  ".mailmap",
+  "FUNDING.yml",
+  "CITATION.cff",
+  CONFIG_FILE,
+];
+
+/// Gitignore like patterns selecting files and folders which are never published.
+///
+/// `*` and `?` stay within a folder, `**` crosses folders, patterns without a slash match at any
+/// depth and a trailing slash only matches folders.
+#[derive(Default)]
+pub(crate) struct ExcludeList {
+  patterns: Vec<Pattern>,
+}
+
+struct Pattern {
+  regex: Regex,
+  directory_only: bool,
+}
+
+impl ExcludeList {
+  pub(crate) fn new(patterns: &[String]) -> anyhow::Result<Self> {
+    Ok(ExcludeList {
+      patterns: patterns
+        .iter()
+        .map(|pattern| Pattern::new(pattern))
+        .collect::<anyhow::Result<Vec<Pattern>>>()?,
+    })
+  }
+
+  /// Adds the default excludes to `patterns`, except the ones listed in `keep`.
+  pub(crate) fn with_defaults(patterns: &[String], keep: &[String]) -> anyhow::Result<Self> {
+    let name = |pattern: &str| pattern.trim().trim_matches('/').to_string();
+
+    if let Some(unknown) = keep.iter().find(|kept| {
+      !DEFAULT_EXCLUDES
+        .iter()
+        .any(|default| name(default) == name(kept))
+    }) {
+      return Err(anyhow!(
+        "cannot keep {unknown:?}, only {} are excluded by default",
+        DEFAULT_EXCLUDES.join(", ")
+      ));
+    }
+
+    let mut list = Self::new(patterns)?;
+    for default in DEFAULT_EXCLUDES {
+      if !keep.iter().any(|kept| name(kept) == name(default)) {
+        list.patterns.push(Pattern::new(default)?);
+      }
+    }
+
+    Ok(list)
+  }
+
+  pub(crate) fn is_empty(&self) -> bool {
+    self.patterns.is_empty()
+  }
+
+  /// Checks a path relative to the root of the site.
+  pub(crate) fn matches(&self, path: &Path, is_dir: bool) -> bool {
+    let path = path.to_string_lossy();
+
+    self
+      .patterns
+      .iter()
+      .any(|pattern| (is_dir || !pattern.directory_only) && pattern.regex.is_match(&path))
+  }
+}
+
+impl Pattern {
+  fn new(pattern: &str) -> anyhow::Result<Self> {
+    let trimmed = pattern.trim();
+    let directory_only = trimmed.ends_with('/');
+    let glob = trimmed.trim_end_matches('/');
+    // like in gitignore a slash anywhere but at the end anchors the pattern to the root
+    let anchored = glob.contains('/');
+    let glob = glob.trim_start_matches('/');
+
+    if glob.is_empty() {
+      return Err(anyhow!("exclude pattern {pattern:?} is empty"));
+    }
+
+    if glob.split('/').any(|part| part == "..") {
+      return Err(anyhow!(
+        "exclude pattern {pattern:?} points outside of the repository"
+      ));
+    }
+
+    let mut regex = String::from(if anchored { "^" } else { "^(?:.*/)?" });
+    let mut chars = glob.chars().peekable();
+    while let Some(c) = chars.next() {
+      match c {
+        '*' if chars.peek() == Some(&'*') => {
+          chars.next();
+          if chars.peek() == Some(&'/') {
+            chars.next();
+            regex.push_str("(?:.*/)?");
+          } else {
+            regex.push_str(".*");
+          }
+        }
+        '*' => regex.push_str("[^/]*"),
+        '?' => regex.push_str("[^/]"),
+        c => regex.push_str(&regex::escape(&c.to_string())),
+      }
+    }
+    regex.push('$');
+
+    Ok(Pattern {
+      regex: Regex::new(&regex)?,
+      directory_only,
+    })
+  }
+}
+
+/// Deletes every file and folder below `root` matching the list, returns the removed paths relative
+/// to `root`. Symlinks are removed themselves and never followed.
+pub(crate) async fn remove_excluded(
+  root: &Path,
+  exclude: &ExcludeList,
+) -> anyhow::Result<Vec<PathBuf>> {
+  let mut removed = Vec::new();
+  if exclude.is_empty() {
+    return Ok(removed);
+  }
+
+  let mut directories = vec![root.to_path_buf()];
+  while let Some(directory) = directories.pop() {
+    let mut entries = tokio::fs::read_dir(&directory).await?;
+
+    while let Some(entry) = entries.next_entry().await? {
+      let is_dir = entry.file_type().await?.is_dir();
+      let path = entry.path();
+      let relative = path.strip_prefix(root)?.to_path_buf();
+
+      if !exclude.matches(&relative, is_dir) {
+        if is_dir {
+          directories.push(path);
+        }
+        continue;
+      }
+
+      if is_dir {
+        tokio::fs::remove_dir_all(&path).await?;
+      } else {
+        tokio::fs::remove_file(&path).await?;
+      }
+      removed.push(relative);
+    }
+  }
+
+  removed.sort();
+
+  Ok(removed)
+}
+
+#[cfg(test)]
+mod tests {
+  use std::path::{Path, PathBuf};
+
+  use crate::service::exclude::{remove_excluded, ExcludeList, DEFAULT_EXCLUDES};
+
+  fn list(patterns: &[&str]) -> ExcludeList {
+    ExcludeList::new(
+      &patterns
+        .iter()
+        .map(|pattern| pattern.to_string())
+        .collect::<Vec<_>>(),
+    )
+    .unwrap()
+  }
+
+  #[test]
+  fn test_matches_patterns() {
+    let exclude = list(&["*.csv", "/build", "data/raw/", "notes/**/*.md"]);
+
+    assert!(exclude.matches(Path::new("results.csv"), false));
+    assert!(exclude.matches(Path::new("deep/folder/results.csv"), false));
+    assert!(!exclude.matches(Path::new("results.csv.html"), false));
+
+    assert!(exclude.matches(Path::new("build"), true));
+    assert!(!exclude.matches(Path::new("src/build"), true));
+
+    assert!(exclude.matches(Path::new("data/raw"), true));
+    assert!(!exclude.matches(Path::new("data/raw"), false));
+
+    assert!(exclude.matches(Path::new("notes/todo.md"), false));
+    assert!(exclude.matches(Path::new("notes/a/b/todo.md"), false));
+    assert!(!exclude.matches(Path::new("docs/notes/todo.md"), false));
+  }
+
+  #[test]
+  fn test_rejects_invalid_patterns() {
+    assert!(ExcludeList::new(&["/".to_string()]).is_err());
+    assert!(ExcludeList::new(&["../secret".to_string()]).is_err());
+    assert!(ExcludeList::new(&[]).unwrap().is_empty());
+  }
+
+  #[test]
+  fn test_applies_defaults() {
+    let exclude = ExcludeList::with_defaults(&[], &["CITATION.cff".to_string()]).unwrap();
+
+    assert!(exclude.matches(Path::new(".github"), true));
+    assert!(exclude.matches(Path::new("docs/CODEOWNERS"), false));
+    assert!(exclude.matches(Path::new(".doubleblind.toml"), false));
+    assert!(!exclude.matches(Path::new("CITATION.cff"), false));
+    assert!(!exclude.matches(Path::new("index.html"), false));
+
+    let everything = DEFAULT_EXCLUDES.map(String::from);
+    assert!(ExcludeList::with_defaults(&[], &everything)
+      .unwrap()
+      .is_empty());
+    assert!(ExcludeList::with_defaults(&[], &["README.md".to_string()]).is_err());
+  }
+
+  #[tokio::test]
+  async fn test_removes_excluded() {
+    let dir = tempfile::tempdir().unwrap();
+    let root = dir.path();
+    tokio::fs::create_dir_all(root.join("data/raw"))
+      .await
+      .unwrap();
+    tokio::fs::write(root.join("data/raw/a.bin"), "a")
+      .await
+      .unwrap();
+    tokio::fs::write(root.join("data/summary.csv"), "b")
+      .await
+      .unwrap();
+    tokio::fs::write(root.join("index.html"), "c")
+      .await
+      .unwrap();
+
+    let removed = remove_excluded(root, &list(&["raw/", "*.csv"]))
+      .await
+      .unwrap();
+
+    assert_eq!(
+      removed,
+      vec![PathBuf::from("data/raw"), PathBuf::from("data/summary.csv")]
+    );
+    assert!(root.join("index.html").exists());
+    assert!(root.join("data").exists());
+  }
+}
@@ -0,0 +1,128 @@
+use std::sync::Arc;
+use std::time::Duration;
+
+use sea_orm::entity::EntityTrait;
+use sea_orm::{ActiveModelTrait, ColumnTrait, DatabaseConnection, QueryFilter, Set, Unchanged};
+use time::OffsetDateTime;
+use tracing::{error, info};
+
+use entity::repository::ExpiryAction;
+use entity::{access_code, repository};
+
+use crate::service::deploy::DeploymentService;
+
+/// how often the schedules of the repositories are checked
+const EXPIRY_INTERVAL: Duration = Duration::from_secs(60);
+
+/// Takes sites down or makes them public once their review period is over.
+#[derive(Clone)]
+pub(crate) struct ExpiryService {
+  db: Arc<DatabaseConnection>,
+  deployment_service: DeploymentService,
+}
+
+impl ExpiryService {
+  pub(crate) fn new(db: Arc<DatabaseConnection>, deployment_service: DeploymentService) -> Self {
+    Self {
+      db,
+      deployment_service,
+    }
+  }
+
+  pub(crate) async fn expiry_loop(&self) -> anyhow::Result<()> {
+    let mut interval = tokio::time::interval(EXPIRY_INTERVAL);
+
+    loop {
+      interval.tick().await;
+
+      if let Err(e) = self.expire_sites().await {
+        error!("error while expiring sites {e:#}");
+      }
+    }
+  }
+
+  /// Applies the expiry action of every repository whose expiry has passed and brings the sites
+  /// online whose publish date has been reached.
+  async fn expire_sites(&self) -> anyhow::Result<()> {
+    let now = OffsetDateTime::now_utc();
+
+    let expired = repository::Entity::find()
+      .filter(repository::Column::ExpiresAt.lte(now))
+      .all(&*self.db)
+      .await?;
+
+    for repository in expired {
+      // a site which cannot be expired must not hold up the others, it is retried on the next tick
+      if let Err(e) = self.expire_site(&repository).await {
+        error!("error while expiring {} {e:#}", repository.github_full_name);
+        continue;
+      }
+    }
+
+    let scheduled = repository::Entity::find()
+      .filter(repository::Column::PublishAfter.lte(now))
+      .all(&*self.db)
+      .await?;
+
+    for repository in scheduled {
+      if let Err(e) = self.publish_site(&repository).await {
+        error!(
+          "error while publishing {} {e:#}",
+          repository.github_full_name
+        );
+        continue;
+      }
+    }
+
+    Ok(())
+  }
+
+  /// Activates the site which was kept offline until its publish date, the date is only cleared once
+  /// this worked so a failure is retried on the next tick.
+  async fn publish_site(&self, repository: &repository::Model) -> anyhow::Result<()> {
+    let repository = repository::Model {
+      publish_after: None,
+      ..repository.clone()
+    };
+    self.deployment_service.apply_schedule(&repository).await?;
+
+    repository::ActiveModel {
+      id: Unchanged(repository.id),
+      publish_after: Set(None),
+      ..Default::default()
+    }
+    .update(&*self.db)
+    .await?;
+
+    Ok(())
+  }
+
+  async fn expire_site(&self, repository: &repository::Model) -> anyhow::Result<()> {
+    match repository.expiry_action {
+      ExpiryAction::Unpublish => {
+        self.deployment_service.unpublish(repository).await?;
+      }
+      ExpiryAction::Public => {
+        access_code::Entity::delete_many()
+          .filter(access_code::Column::Repository.eq(repository.id))
+          .exec(&*self.db)
+          .await?;
+      }
+    }
+
+    info!(
+      "Review period of {} is over, applied {:?}",
+      repository.github_full_name, repository.expiry_action
+    );
+
+    repository::ActiveModel {
+      id: Unchanged(repository.id),
+      expires_at: Set(None),
+      ..Default::default()
+    }
+    .update(&*self.db)
+    .await?;
+
+    Ok(())
+  }
+}
@@ -0,0 +1,577 @@
+use std::io;
+use std::path::{Component, Path, PathBuf};
+
+use anyhow::anyhow;
+use futures_util::StreamExt;
+use tokio::io::{AsyncRead, AsyncReadExt};
+use tokio_tar::{Archive, EntryType};
+use tracing::warn;
+
+use crate::service::config::CONFIG_FILE;
+
+/// upper bounds for what a single repository tarball may unpack to
+#[derive(Clone, Debug)]
+pub(crate) struct ExtractionLimits {
+  pub(crate) max_total_size: u64,
+  pub(crate) max_files: u64,
+  pub(crate) max_file_size: u64,
+}
+
+/// Unpacks a github tarball into `target`, dropping the folder github wraps every repository into,
+/// and returns the number of bytes written. With a `subtree` only the entries below it and the
+/// config file are unpacked and counted against the limits.
+///
+/// Entries escaping `target` fail the extraction, links pointing outside of it are dropped and
+/// devices or fifos are skipped, so nothing outside of the site can be read or written. Nothing is
+/// ever written through a symlink, so link chains cannot be abused to escape either.
+pub(crate) async fn extract_archive<R: AsyncRead + Unpin>(
+  archive: &mut Archive<R>,
+  target: &Path,
+  subtree: Option<&Path>,
+  limits: &ExtractionLimits,
+) -> anyhow::Result<u64> {
+  let mut total_size = 0u64;
+  let mut files = 0u64;
+
+  let mut entries = archive.entries()?;
+  while let Some(entry) = entries.next().await {
+    let mut entry = entry?;
+    let entry_type = entry.header().entry_type();
+
+    if entry_type.is_pax_global_extensions() {
+      continue;
+    }
+
+    let raw_path = entry.path()?.into_owned();
+    let path = match site_path(&raw_path)? {
+      Some(path) => path,
+      // the folder github wraps the repository into
+      None => continue,
+    };
+    if !selected(&path, subtree) {
+      continue;
+    }
+    let destination = target.join(&path);
+
+    if behind_symlink(target, &path).await? {
+      return Err(anyhow!("{} is placed behind a symlink", path.display()));
+    }
+
+    match entry_type {
+      EntryType::Directory => {
+        tokio::fs::create_dir_all(&destination).await?;
+        continue;
+      }
+      EntryType::Regular | EntryType::Continuous | EntryType::Symlink | EntryType::Link => {}
+      other => {
+        warn!("skipping {} of type {:?}", raw_path.display(), other);
+        continue;
+      }
+    }
+
+    files += 1;
+    if files > limits.max_files {
+      return Err(anyhow!(
+        "repository contains more than {} files",
+        limits.max_files
+      ));
+    }
+
+    if let Some(parent) = destination.parent() {
+      tokio::fs::create_dir_all(parent).await?;
+    }
+
+    // later entries replace earlier ones, without following them if they are symlinks
+    match tokio::fs::symlink_metadata(&destination).await {
+      Ok(metadata) if metadata.is_dir() => {
+        return Err(anyhow!("{} replaces a directory", path.display()));
+      }
+      Ok(_) => tokio::fs::remove_file(&destination).await?,
+      Err(e) if e.kind() == io::ErrorKind::NotFound => {}
+      Err(e) => return Err(e.into()),
+    }
+
+    match entry_type {
+      EntryType::Symlink => {
+        let link = entry
+          .link_name()?
+          .ok_or_else(|| anyhow!("symlink {} has no target", raw_path.display()))?
+          .into_owned();
+
+        // relative to the folder containing the symlink, it has to stay inside of the site
+        let resolved = path.parent().unwrap_or(Path::new("")).join(&link);
+        if link.is_absolute() || normalize(&resolved).is_none() {
+          warn!(
+            "dropping symlink {} pointing outside of the site to {}",
+            raw_path.display(),
+            link.display()
+          );
+          continue;
+        }
+
+        tokio::fs::symlink(&link, &destination).await?;
+      }
+      EntryType::Link => {
+        let link = entry
+          .link_name()?
+          .ok_or_else(|| anyhow!("hardlink {} has no target", raw_path.display()))?
+          .into_owned();
+
+        // hardlinks are relative to the root of the archive
+        let source = match site_path(&link) {
+          Ok(Some(source))
+            if selected(&source, subtree) && !behind_symlink(target, &source).await? =>
+          {
+            target.join(source)
+          }
+          _ => {
+            warn!(
+              "dropping hardlink {} pointing outside of the site to {}",
+              raw_path.display(),
+              link.display()
+            );
+            continue;
+          }
+        };
+
+        tokio::fs::hard_link(&source, &destination).await?;
+      }
+      _ => {
+        let size = entry.header().size()?;
+        if size > limits.max_file_size {
+          return Err(anyhow!(
+            "{} is larger than the limit of {} bytes per file",
+            path.display(),
+            limits.max_file_size
+          ));
+        }
+
+        total_size += size;
+        if total_size > limits.max_total_size {
+          return Err(anyhow!(
+            "repository is larger than the limit of {} bytes",
+            limits.max_total_size
+          ));
+        }
+
+        // never trust the size from the header while writing
+        let mut file = tokio::fs::File::create(&destination).await?;
+        let written = tokio::io::copy(&mut (&mut entry).take(size), &mut file).await?;
+        if written != size {
+          return Err(anyhow!("{} is truncated", path.display()));
+        }
+      }
+    }
+  }
+
+  // the lexical check above cannot see through other symlinks, the file system can
+  contain_symlinks(target).await?;
+
+  Ok(total_size)
+}
+
+/// Resolves the folder of an unpacked repository which gets published. Symlinks leaving it are
+/// dropped, once moved into the release folder they would point into neighbouring releases.
+pub(crate) async fn site_root(
+  repository: &Path,
+  subdirectory: Option<&Path>,
+) -> anyhow::Result<PathBuf> {
+  let subdirectory = match subdirectory {
+    Some(value) => value,
+    None => {
+      contain_symlinks(repository).await?;
+      return Ok(repository.to_path_buf());
+    }
+  };
+  let root = repository.join(subdirectory);
+
+  if behind_symlink(repository, subdirectory).await? {
+    return Err(anyhow!(
+      "{} is placed behind a symlink",
+      subdirectory.display()
+    ));
+  }
+
+  match tokio::fs::symlink_metadata(&root).await {
+    Ok(metadata) if metadata.is_dir() => {}
+    Ok(_) => return Err(anyhow!("{} is not a directory", subdirectory.display())),
+    Err(e) if e.kind() == io::ErrorKind::NotFound => {
+      return Err(anyhow!(
+        "{} does not exist in the repository",
+        subdirectory.display()
+      ));
+    }
+    Err(e) => return Err(e.into()),
+  }
+
+  contain_symlinks(&root).await?;
+
+  Ok(root)
+}
+
+/// Removes every symlink below `root` which does not resolve to something inside of it.
+async fn contain_symlinks(root: &Path) -> anyhow::Result<()> {
+  let canonical_root = tokio::fs::canonicalize(root).await?;
+  let mut directories = vec![root.to_path_buf()];
+
+  while let Some(directory) = directories.pop() {
+    let mut entries = tokio::fs::read_dir(&directory).await?;
+
+    while let Some(entry) = entries.next_entry().await? {
+      let file_type = entry.file_type().await?;
+      let path = entry.path();
+
+      if file_type.is_dir() {
+        directories.push(path);
+        continue;
+      }
+
+      if !file_type.is_symlink() {
+        continue;
+      }
+
+      match tokio::fs::canonicalize(&path).await {
+        Ok(resolved) if resolved.starts_with(&canonical_root) => {}
+        _ => {
+          warn!(
+            "dropping symlink {} which does not resolve inside of the site",
+            path.display()
+          );
+          tokio::fs::remove_file(&path).await?;
+        }
+      }
+    }
+  }
+
+  Ok(())
+}
+
+/// Validates a user supplied folder inside of the repository, `None` stands for the whole repository.
+pub(crate) fn subdirectory(path: &str) -> anyhow::Result<Option<PathBuf>> {
+  let normalized = normalize(Path::new(path.trim_start_matches('/')))
+    .ok_or_else(|| anyhow!("{} points outside of the repository", path))?;
+
+  if normalized.as_os_str().is_empty() {
+    Ok(None)
+  } else {
+    Ok(Some(normalized))
+  }
+}
+
+/// Checks whether any parent folder of `path` inside of `target` is a symlink.
+async fn behind_symlink(target: &Path, path: &Path) -> io::Result<bool> {
+  let mut current = target.to_path_buf();
+
+  for component in path.parent().unwrap_or(Path::new("")).components() {
+    current.push(component);
+
+    match tokio::fs::symlink_metadata(&current).await {
+      Ok(metadata) if metadata.is_symlink() => return Ok(true),
+      Ok(_) => {}
+      Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(false),
+      Err(e) => return Err(e),
+    }
+  }
+
+  Ok(false)
+}
+
+/// Checks whether an entry is unpacked if only `subtree` gets published, the config file in the
+/// root of the repository is needed either way.
+fn selected(path: &Path, subtree: Option<&Path>) -> bool {
+  match subtree {
+    Some(subtree) => path.starts_with(subtree) || path == Path::new(CONFIG_FILE),
+    None => true,
+  }
+}
+
+/// Strips the folder github adds in front of every path, returns an error if the path tries to
+/// escape the site and `None` for the wrapping folder itself.
+fn site_path(path: &Path) -> anyhow::Result<Option<PathBuf>> {
+  let normalized = normalize(path)
+    .ok_or_else(|| anyhow!("{} points outside of the repository", path.display()))?;
+
+  let mut components = normalized.components();
+  components.next();
+
+  let stripped = components.as_path();
+  if stripped.as_os_str().is_empty() {
+    Ok(None)
+  } else {
+    Ok(Some(stripped.to_path_buf()))
+  }
+}
+
+/// Lexically resolves `.` and `..` in a relative path, `None` if it is absolute or leaves its root.
+fn normalize(path: &Path) -> Option<PathBuf> {
+  let mut normalized = PathBuf::new();
+
+  for component in path.components() {
+    match component {
+      Component::Normal(part) => normalized.push(part),
+      Component::CurDir => {}
+      Component::ParentDir => {
+        if !normalized.pop() {
+          return None;
+        }
+      }
+      Component::RootDir | Component::Prefix(_) => return None,
+    }
+  }
+
+  Some(normalized)
+}
+
+#[cfg(test)]
+mod tests {
+  use std::path::{Path, PathBuf};
+
+  use tempfile::TempDir;
+  use tokio_tar::{Archive, Builder, EntryType, Header};
+
+  use crate::service::extract::{extract_archive, site_root, subdirectory, ExtractionLimits};
+
+  const LIMITS: ExtractionLimits = ExtractionLimits {
+    max_total_size: 64,
+    max_files: 4,
+    max_file_size: 32,
+  };
+
+  fn header(path: &str, entry_type: EntryType, size: u64) -> Header {
+    let mut header = Header::new_gnu();
+    // written by hand, the builder would refuse the malicious paths we want to test
+    header.as_old_mut().name[..path.len()].copy_from_slice(path.as_bytes());
+    header.set_entry_type(entry_type);
+    header.set_mode(0o644);
+    header.set_size(size);
+    header.set_cksum();
+    header
+  }
+
+  async fn tarball(entries: Vec<(Header, &[u8])>) -> Vec<u8> {
+    let mut builder = Builder::new(Vec::new());
+    for (header, data) in entries {
+      builder.append(&header, data).await.unwrap();
+    }
+    builder.into_inner().await.unwrap()
+  }
+
+  async fn extract(entries: Vec<(Header, &[u8])>) -> (TempDir, anyhow::Result<u64>) {
+    let target = tempfile::tempdir().unwrap();
+
+    let data = tarball(entries).await;
+    let mut archive = Archive::new(data.as_slice());
+    let result = extract_archive(&mut archive, target.path(), None, &LIMITS).await;
+
+    (target, result)
+  }
+
+  #[tokio::test]
+  async fn test_strips_github_folder() {
+    let (target, result) = extract(vec![
+      (header("repo-abc/", EntryType::Directory, 0), &[]),
+      (
+        header("repo-abc/index.html", EntryType::Regular, 5),
+        b"hello",
+      ),
+    ])
+    .await;
+
+    assert_eq!(result.unwrap(), 5);
+    assert_eq!(
+      tokio::fs::read_to_string(target.path().join("index.html"))
+        .await
+        .unwrap(),
+      "hello"
+    );
+  }
+
+  #[tokio::test]
+  async fn test_rejects_path_traversal() {
+    let (target, result) = extract(vec![(
+      header("repo-abc/../../evil", EntryType::Regular, 5),
+      b"hello",
+    )])
+    .await;
+
+    assert!(result.is_err());
+    assert!(!target.path().join("../evil").exists());
+  }
+
+  #[tokio::test]
+  async fn test_drops_escaping_symlinks() {
+    let mut outside = header("repo-abc/passwd", EntryType::Symlink, 0);
+    outside.set_link_name("../../../etc/passwd").unwrap();
+    outside.set_cksum();
+    let mut inside = header("repo-abc/docs/start", EntryType::Symlink, 0);
+    inside.set_link_name("../index.html").unwrap();
+    inside.set_cksum();
+
+    let (target, result) = extract(vec![
+      (
+        header("repo-abc/index.html", EntryType::Regular, 5),
+        b"hello",
+      ),
+      (outside, &[]),
+      (inside, &[]),
+    ])
+    .await;
+
+    assert!(result.is_ok());
+    assert!(tokio::fs::symlink_metadata(target.path().join("passwd"))
+      .await
+      .is_err());
+    assert!(
+      tokio::fs::symlink_metadata(target.path().join("docs/start"))
+        .await
+        .is_ok()
+    );
+  }
+
+  #[tokio::test]
+  async fn test_rejects_symlink_chains() {
+    let mut folder = header("repo-abc/a/b/c", EntryType::Symlink, 0);
+    folder.set_link_name("../..").unwrap();
+    folder.set_cksum();
+    let mut escape = header("repo-abc/escape", EntryType::Symlink, 0);
+    escape.set_link_name("a/b/c/../..").unwrap();
+    escape.set_cksum();
+
+    let (target, result) = extract(vec![(folder, &[]), (escape, &[])]).await;
+
+    assert!(result.is_ok());
+    assert!(tokio::fs::symlink_metadata(target.path().join("escape"))
+      .await
+      .is_err());
+
+    let mut folder = header("repo-abc/a/b/c", EntryType::Symlink, 0);
+    folder.set_link_name(".").unwrap();
+    folder.set_cksum();
+    let (_other, result) = extract(vec![
+      (folder, &[]),
+      (
+        header("repo-abc/a/b/c/file", EntryType::Regular, 5),
+        b"hello",
+      ),
+    ])
+    .await;
+    assert!(result.is_err());
+  }
+
+  #[tokio::test]
+  async fn test_enforces_limits() {
+    let large = [0u8; 40];
+    let (_target, result) = extract(vec![(
+      header("repo-abc/large.bin", EntryType::Regular, 40),
+      &large,
+    )])
+    .await;
+    assert!(result.is_err());
+
+    let file = [0u8; 8];
+    let (_target, result) = extract(
+      (0..5)
+        .map(|i| {
+          (
+            header(&format!("repo-abc/{i}"), EntryType::Regular, 8),
+            &file[..],
+          )
+        })
+        .collect(),
+    )
+    .await;
+    assert!(result.is_err());
+  }
+
+  #[tokio::test]
+  async fn test_skips_special_files() {
+    let (target, result) = extract(vec![(header("repo-abc/pipe", EntryType::Fifo, 0), &[])]).await;
+
+    assert!(result.is_ok());
+    assert!(!target.path().join("pipe").exists());
+  }
+
+  #[tokio::test]
+  async fn test_selects_subdirectory() {
+    let mut raw = header("repo-abc/docs/raw.csv", EntryType::Symlink, 0);
+    raw.set_link_name("../data.csv").unwrap();
+    raw.set_cksum();
+    let mut logo = header("repo-abc/docs/logo.svg", EntryType::Symlink, 0);
+    logo.set_link_name("index.html").unwrap();
+    logo.set_cksum();
+
+    let (target, result) = extract(vec![
+      (header("repo-abc/data.csv", EntryType::Regular, 3), b"a,b"),
+      (
+        header("repo-abc/docs/index.html", EntryType::Regular, 5),
+        b"hello",
+      ),
+      (raw, &[]),
+      (logo, &[]),
+    ])
+    .await;
+    assert!(result.is_ok());
+
+    let root = site_root(target.path(), Some(Path::new("docs")))
+      .await
+      .unwrap();
+    assert_eq!(root, target.path().join("docs"));
+    assert!(tokio::fs::symlink_metadata(root.join("raw.csv"))
+      .await
+      .is_err());
+    assert!(tokio::fs::symlink_metadata(root.join("logo.svg"))
+      .await
+      .is_ok());
+
+    assert!(site_root(target.path(), Some(Path::new("missing")))
+      .await
+      .is_err());
+    assert!(site_root(target.path(), Some(Path::new("data.csv")))
+      .await
+      .is_err());
+
+    assert_eq!(
+      subdirectory("/docs/./site/").unwrap(),
+      Some(PathBuf::from("docs/site"))
+    );
+    assert_eq!(subdirectory("/").unwrap(), None);
+    assert!(subdirectory("docs/../../etc").is_err());
+  }
+
+  #[tokio::test]
+  async fn test_extracts_only_subtree() {
+    let target = tempfile::tempdir().unwrap();
+    let large = [0u8; 40];
+    let mut outside = header("repo-abc/docs/data.bin", EntryType::Link, 0);
+    outside.set_link_name("repo-abc/large.bin").unwrap();
+    outside.set_cksum();
+
+    let data = tarball(vec![
+      (header("repo-abc/large.bin", EntryType::Regular, 40), &large),
+      (
+        header("repo-abc/docs/index.html", EntryType::Regular, 5),
+        b"hello",
+      ),
+      (
+        header("repo-abc/.doubleblind.toml", EntryType::Regular, 2),
+        b"#\n",
+      ),
+      (outside, &[]),
+    ])
+    .await;
+    let mut archive = Archive::new(data.as_slice());
+    let result = extract_archive(
+      &mut archive,
+      target.path(),
+      Some(Path::new("docs")),
+      &LIMITS,
+    )
+    .await;
+
+    assert_eq!(result.unwrap(), 7);
+    assert!(target.path().join("docs/index.html").exists());
+    assert!(target.path().join(".doubleblind.toml").exists());
+    assert!(!target.path().join("large.bin").exists());
+    assert!(!target.path().join("docs/data.bin").exists());
+  }
+}
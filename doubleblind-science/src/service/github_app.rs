@@ -3,16 +3,18 @@ use std::sync::Arc;
 use sea_orm::entity::EntityTrait;
 use sea_orm::QueryFilter;
 use sea_orm::Set;
-use sea_orm::{ActiveModelTrait, DatabaseConnection};
-use sea_orm::{ColumnTrait, NotSet};
-use sea_query::Expr;
+use sea_orm::{ActiveModelTrait, DatabaseConnection, QueryOrder, TransactionTrait};
+use sea_orm::{ColumnTrait, NotSet, Unchanged};
+use sea_query::{Expr, Func, OnConflict};
 use time::OffsetDateTime;
 use uuid::Uuid;
 
 use crate::routes::GithubRepoEdit;
+use crate::service::domain::random_domain;
 use entity::github_app::Model;
 use entity::prelude::Repository;
-use entity::{github_app, repository};
+use entity::repository::{CameraReadyLink, ExpiryAction, SiteFallback};
+use entity::{github_app, redaction_term, repository};
 
 #[derive(Clone)]
 pub(crate) struct ProjectService {
@@ -43,11 +45,26 @@ impl ProjectService {
       .one(&*self.db)
       .await?
     {
+      // installed again, its repositories can be deployed again
+      Some(value) if value.suspended_at.is_some() || value.removed_at.is_some() => Ok(
+        github_app::ActiveModel {
+          id: Unchanged(value.id),
+          suspended_at: Set(None),
+          removed_at: Set(None),
+          last_update: Set(OffsetDateTime::now_utc()),
+          ..Default::default()
+        }
+        .update(&*self.db)
+        .await?,
+      ),
       Some(value) => Ok(value),
       None => Ok(
         github_app::ActiveModel {
           id: Set(Uuid::new_v4()),
           installation_id: Set(installation_id),
+          quota: Set(None),
+          suspended_at: Set(None),
+          removed_at: Set(None),
           last_update: Set(OffsetDateTime::now_utc()),
         }
         .insert(&*self.db)
@@ -56,6 +73,88 @@ impl ProjectService {
     }
   }
 
+  /// Overrides the disk quota of the installation, `None` falls back to the default quota. Returns
+  /// nothing if the installation is unknown.
+  pub(crate) async fn set_quota(
+    &self,
+    installation_id: i64,
+    quota: Option<i64>,
+  ) -> anyhow::Result<Option<Model>> {
+    let github_app = match self.get_github_app(installation_id).await? {
+      Some(value) => value,
+      None => return Ok(None),
+    };
+
+    Ok(Some(
+      github_app::ActiveModel {
+        id: Unchanged(github_app.id),
+        quota: Set(quota),
+        last_update: Set(OffsetDateTime::now_utc()),
+        ..Default::default()
+      }
+      .update(&*self.db)
+      .await?,
+    ))
+  }
+
+  /// Marks the installation as suspended or active again, returns it together with its repositories.
+  pub(crate) async fn set_github_app_suspended(
+    &self,
+    installation_id: i64,
+    suspended: bool,
+  ) -> anyhow::Result<Option<(Model, Vec<repository::Model>)>> {
+    let github_app = match self.get_github_app(installation_id).await? {
+      Some(value) => value,
+      None => return Ok(None),
+    };
+
+    let github_app = github_app::ActiveModel {
+      id: Unchanged(github_app.id),
+      suspended_at: Set(suspended.then(OffsetDateTime::now_utc)),
+      last_update: Set(OffsetDateTime::now_utc()),
+      ..Default::default()
+    }
+    .update(&*self.db)
+    .await?;
+
+    self.with_repositories(github_app).await.map(Some)
+  }
+
+  /// Marks the installation as uninstalled, what happens to its repositories depends on the suspension
+  /// policy.
+  pub(crate) async fn remove_github_app(
+    &self,
+    installation_id: i64,
+  ) -> anyhow::Result<Option<(Model, Vec<repository::Model>)>> {
+    let github_app = match self.get_github_app(installation_id).await? {
+      Some(value) => value,
+      None => return Ok(None),
+    };
+
+    let github_app = github_app::ActiveModel {
+      id: Unchanged(github_app.id),
+      removed_at: Set(Some(OffsetDateTime::now_utc())),
+      last_update: Set(OffsetDateTime::now_utc()),
+      ..Default::default()
+    }
+    .update(&*self.db)
+    .await?;
+
+    self.with_repositories(github_app).await.map(Some)
+  }
+
+  async fn with_repositories(
+    &self,
+    github_app: Model,
+  ) -> anyhow::Result<(Model, Vec<repository::Model>)> {
+    let repositories = repository::Entity::find()
+      .filter(repository::Column::GithubApp.eq(github_app.id))
+      .all(&*self.db)
+      .await?;
+
+    Ok((github_app, repositories))
+  }
+
   pub(crate) async fn get_repository(&self, id: i64) -> anyhow::Result<Option<repository::Model>> {
     Ok(
       repository::Entity::find()
@@ -65,10 +164,17 @@ impl ProjectService {
     )
   }
 
+  pub(crate) async fn get_repository_uuid(
+    &self,
+    id: Uuid,
+  ) -> anyhow::Result<Option<repository::Model>> {
+    Ok(repository::Entity::find_by_id(id).one(&*self.db).await?)
+  }
+
   pub(crate) async fn all_repos_for_installation_id(
     &self,
     installation_id: i64,
-  ) -> anyhow::Result<Option<Vec<repository::Model>>> {
+  ) -> anyhow::Result<Option<(Model, Vec<repository::Model>)>> {
     let found_github_app: Model = match github_app::Entity::find()
       .filter(github_app::Column::InstallationId.eq(installation_id))
       .one(&*self.db)
@@ -80,12 +186,12 @@ impl ProjectService {
       }
     };
 
-    Ok(Some(
-      repository::Entity::find()
-        .filter(repository::Column::GithubApp.eq(found_github_app.id))
-        .all(&*self.db)
-        .await?,
-    ))
+    let repositories = repository::Entity::find()
+      .filter(repository::Column::GithubApp.eq(found_github_app.id))
+      .all(&*self.db)
+      .await?;
+
+    Ok(Some((found_github_app, repositories)))
   }
 
   pub(crate) async fn rewrite_list_of_repositories(
@@ -94,11 +200,11 @@ impl ProjectService {
     add: Vec<GithubRepoEdit>,
     remove: Vec<GithubRepoEdit>,
   ) -> anyhow::Result<()> {
-    for x in remove {
-      repository::Entity::delete_many()
-        .filter(repository::Column::GithubId.eq(x.id))
-        .exec(&*self.db)
-        .await?;
+    self.remove_repositories(remove).await?;
+
+    // inserting nothing is not a valid statement
+    if add.is_empty() {
+      return Ok(());
     }
 
     Repository::insert_many(add.into_iter().map(|info| repository::ActiveModel {
@@ -106,31 +212,222 @@ impl ProjectService {
       github_app: Set(app_id),
       domain: NotSet,
       branch: NotSet,
+      path: NotSet,
       github_id: Set(info.id),
       github_short_name: Set(info.name),
       github_full_name: Set(info.full_name),
       trusted: Set(false),
       deployed: Set(false),
+      disk_usage: Set(0),
+      camera_ready: Set(false),
+      camera_ready_link: Set(None),
+      publish_after: Set(None),
+      expires_at: Set(None),
+      expiry_action: Set(ExpiryAction::Unpublish),
+      fallback: Set(SiteFallback::NotFound),
       created_at: Set(OffsetDateTime::now_utc()),
       last_update: Set(OffsetDateTime::now_utc()),
     }))
+    // repositories kept from an earlier installation move over with their site
+    .on_conflict(
+      OnConflict::column(repository::Column::GithubId)
+        .update_columns([
+          repository::Column::GithubApp,
+          repository::Column::GithubShortName,
+          repository::Column::GithubFullName,
+          repository::Column::LastUpdate,
+        ])
+        .to_owned(),
+    )
     .exec(&*self.db)
     .await?;
 
     Ok(())
   }
 
+  pub(crate) async fn remove_repositories(
+    &self,
+    remove: Vec<GithubRepoEdit>,
+  ) -> anyhow::Result<()> {
+    for x in remove {
+      repository::Entity::delete_many()
+        .filter(repository::Column::GithubId.eq(x.id))
+        .exec(&*self.db)
+        .await?;
+    }
+
+    Ok(())
+  }
+
+  /// Stores the current names of the repository on github and moves it to the given installation
+  /// if that one is known, returns the repository unless it was never added.
+  pub(crate) async fn update_repository(
+    &self,
+    info: GithubRepoEdit,
+    installation_id: Option<i64>,
+  ) -> anyhow::Result<Option<repository::Model>> {
+    let repository = match self.get_repository(info.id).await? {
+      Some(value) => value,
+      None => return Ok(None),
+    };
+
+    let github_app = match installation_id {
+      Some(installation_id) => self
+        .get_github_app(installation_id)
+        .await?
+        .map(|github_app| github_app.id)
+        .filter(|&id| id != repository.github_app),
+      None => None,
+    };
+
+    if github_app.is_none()
+      && repository.github_short_name == info.name
+      && repository.github_full_name == info.full_name
+    {
+      return Ok(Some(repository));
+    }
+
+    Ok(Some(
+      repository::ActiveModel {
+        id: Unchanged(repository.id),
+        github_app: github_app.map_or(NotSet, Set),
+        github_short_name: Set(info.name),
+        github_full_name: Set(info.full_name),
+        last_update: Set(OffsetDateTime::now_utc()),
+        ..Default::default()
+      }
+      .update(&*self.db)
+      .await?,
+    ))
+  }
+
+  pub(crate) async fn redaction_terms(&self, repository: Uuid) -> anyhow::Result<Vec<String>> {
+    Ok(
+      redaction_term::Entity::find()
+        .filter(redaction_term::Column::Repository.eq(repository))
+        .order_by_asc(redaction_term::Column::CreatedAt)
+        .all(&*self.db)
+        .await?
+        .into_iter()
+        .map(|x| x.term)
+        .collect(),
+    )
+  }
+
+  pub(crate) async fn set_redaction_terms(
+    &self,
+    repository: Uuid,
+    terms: Vec<String>,
+  ) -> anyhow::Result<()> {
+    let txn = self.db.begin().await?;
+
+    redaction_term::Entity::delete_many()
+      .filter(redaction_term::Column::Repository.eq(repository))
+      .exec(&txn)
+      .await?;
+
+    if !terms.is_empty() {
+      redaction_term::Entity::insert_many(terms.into_iter().map(|term| {
+        redaction_term::ActiveModel {
+          id: Set(Uuid::new_v4()),
+          repository: Set(repository),
+          term: Set(term),
+          created_at: Set(OffsetDateTime::now_utc()),
+        }
+      }))
+      .exec(&txn)
+      .await?;
+    }
+
+    txn.commit().await?;
+
+    Ok(())
+  }
+
+  /// Switches between publishing the anonymized and the original content of the repository.
+  pub(crate) async fn set_camera_ready(
+    &self,
+    repository: Uuid,
+    camera_ready: bool,
+    link: Option<CameraReadyLink>,
+  ) -> anyhow::Result<repository::Model> {
+    Ok(
+      repository::ActiveModel {
+        id: Unchanged(repository),
+        camera_ready: Set(camera_ready),
+        camera_ready_link: Set(link),
+        last_update: Set(OffsetDateTime::now_utc()),
+        ..Default::default()
+      }
+      .update(&*self.db)
+      .await?,
+    )
+  }
+
+  /// Sets when the site becomes visible and when it is taken down or made public again.
+  pub(crate) async fn set_schedule(
+    &self,
+    repository: Uuid,
+    publish_after: Option<OffsetDateTime>,
+    expires_at: Option<OffsetDateTime>,
+    expiry_action: ExpiryAction,
+  ) -> anyhow::Result<repository::Model> {
+    Ok(
+      repository::ActiveModel {
+        id: Unchanged(repository),
+        publish_after: Set(publish_after),
+        expires_at: Set(expires_at),
+        expiry_action: Set(expiry_action),
+        last_update: Set(OffsetDateTime::now_utc()),
+        ..Default::default()
+      }
+      .update(&*self.db)
+      .await?,
+    )
+  }
+
+  /// Sets what visitors get for paths the site has no file for.
+  pub(crate) async fn set_fallback(
+    &self,
+    repository: Uuid,
+    fallback: SiteFallback,
+  ) -> anyhow::Result<repository::Model> {
+    Ok(
+      repository::ActiveModel {
+        id: Unchanged(repository),
+        fallback: Set(fallback),
+        last_update: Set(OffsetDateTime::now_utc()),
+        ..Default::default()
+      }
+      .update(&*self.db)
+      .await?,
+    )
+  }
+
+  /// Without a domain the repository keeps its current one or gets a random one.
   pub(crate) async fn deploy_repo(
     &self,
     github_id: i64,
-    domain: String,
+    domain: Option<String>,
     branch: String,
+    path: Option<String>,
   ) -> anyhow::Result<Vec<repository::Model>> {
     Ok(
       Repository::update_many()
         .col_expr(repository::Column::Deployed, Expr::value(true))
-        .col_expr(repository::Column::Domain, Expr::value(domain))
+        .col_expr(
+          repository::Column::Domain,
+          match domain {
+            Some(domain) => Expr::value(domain),
+            None => Func::coalesce([
+              Expr::col(repository::Column::Domain).into(),
+              Expr::value(random_domain()),
+            ])
+            .into(),
+          },
+        )
         .col_expr(repository::Column::Branch, Expr::value(branch))
+        .col_expr(repository::Column::Path, Expr::value(path))
         .filter(repository::Column::GithubId.eq(github_id))
         .exec_with_returning(&*self.db)
         .await?,
@@ -0,0 +1,485 @@
+use std::io::{Cursor, Write};
+use std::path::{Path, PathBuf};
+
+use anyhow::anyhow;
+use lopdf::{Document, Object};
+use zip::write::FileOptions;
+use zip::{ZipArchive, ZipWriter};
+
+/// office open xml parts describing the author, replaced by empty ones since they are referenced
+const OOXML_PARTS: &[(&str, &str)] = &[
+  (
+    "docProps/core.xml",
+    r#"<?xml version="1.0" encoding="UTF-8" standalone="yes"?>
+<cp:coreProperties xmlns:cp="http://schemas.openxmlformats.org/package/2006/metadata/core-properties" xmlns:dc="http://purl.org/dc/elements/1.1/" xmlns:dcterms="http://purl.org/dc/terms/" xmlns:dcmitype="http://purl.org/dc/dcmitype/" xmlns:xsi="http://www.w3.org/2001/XMLSchema-instance"/>"#,
+  ),
+  (
+    "docProps/app.xml",
+    r#"<?xml version="1.0" encoding="UTF-8" standalone="yes"?>
+<Properties xmlns="http://schemas.openxmlformats.org/officeDocument/2006/extended-properties" xmlns:vt="http://schemas.openxmlformats.org/officeDocument/2006/docPropsVTypes"/>"#,
+  ),
+  (
+    "docProps/custom.xml",
+    r#"<?xml version="1.0" encoding="UTF-8" standalone="yes"?>
+<Properties xmlns="http://schemas.openxmlformats.org/officeDocument/2006/custom-properties" xmlns:vt="http://schemas.openxmlformats.org/officeDocument/2006/docPropsVTypes"/>"#,
+  ),
+];
+
+/// open document part describing the author
+const ODF_PARTS: &[(&str, &str)] = &[(
+  "meta.xml",
+  r#"<?xml version="1.0" encoding="UTF-8"?>
+<office:document-meta xmlns:office="urn:oasis:names:tc:opendocument:xmlns:office:1.0" office:version="1.2"><office:meta/></office:document-meta>"#,
+)];
+
+/// rewritten content of a file together with a description of everything removed from it
+type Scrubbed = Option<(Vec<u8>, Vec<String>)>;
+
+/// files which were changed with what was removed from them, and files which were left alone
+/// because their content does not match their name
+#[derive(Default)]
+pub(crate) struct ScrubReport {
+  pub(crate) scrubbed: Vec<(PathBuf, Vec<String>)>,
+  pub(crate) skipped: Vec<PathBuf>,
+}
+
+/// file formats carrying metadata, recognized by the first bytes of their content
+enum Format {
+  Jpeg,
+  Png,
+  Pdf,
+  Zip,
+}
+
+impl Format {
+  fn detect(data: &[u8]) -> Option<Self> {
+    if data.starts_with(&[0xFF, 0xD8, 0xFF]) {
+      Some(Format::Jpeg)
+    } else if data.starts_with(b"\x89PNG\r\n\x1a\n") {
+      Some(Format::Png)
+    } else if data.starts_with(b"PK\x03\x04") {
+      Some(Format::Zip)
+    } else if data[..data.len().min(1024)]
+      .windows(5)
+      .any(|window| window == b"%PDF-")
+    {
+      // readers accept the header anywhere in the first kilobyte
+      Some(Format::Pdf)
+    } else {
+      None
+    }
+  }
+}
+
+/// Removes identifying metadata from images, pdf and office files below `root`. Files are parsed
+/// according to their content, those which cannot be parsed fail the scrubbing since publishing
+/// them unchanged could leak the authors. Files whose content is no format with metadata at all,
+/// like git lfs pointers, are skipped. Blocking, symlinks are not followed.
+pub(crate) fn scrub_tree(root: &Path) -> anyhow::Result<ScrubReport> {
+  let mut report = ScrubReport::default();
+  let mut directories = vec![root.to_path_buf()];
+
+  while let Some(directory) = directories.pop() {
+    for entry in std::fs::read_dir(&directory)? {
+      let entry = entry?;
+      let file_type = entry.file_type()?;
+      let path = entry.path();
+
+      if file_type.is_dir() {
+        directories.push(path);
+        continue;
+      }
+
+      if !file_type.is_file() {
+        continue;
+      }
+
+      let extension = path
+        .extension()
+        .and_then(|extension| extension.to_str())
+        .map(str::to_ascii_lowercase);
+
+      let office_parts = match extension.as_deref() {
+        Some("jpg" | "jpeg" | "png" | "pdf") => None,
+        Some("docx" | "docm" | "xlsx" | "xlsm" | "pptx" | "pptm") => Some(OOXML_PARTS),
+        Some("odt" | "ods" | "odp" | "odg") => Some(ODF_PARTS),
+        _ => continue,
+      };
+
+      let relative = path.strip_prefix(root)?.to_path_buf();
+      let data = std::fs::read(&path)?;
+      let scrubbed = match (Format::detect(&data), office_parts) {
+        (Some(Format::Jpeg), _) => scrub_jpeg(&data),
+        (Some(Format::Png), _) => scrub_png(&data),
+        (Some(Format::Pdf), _) => scrub_pdf(&data),
+        // any zip archive looks alike, only the name tells which parts hold the metadata
+        (Some(Format::Zip), Some(parts)) => scrub_zip(&data, parts),
+        _ => {
+          report.skipped.push(relative);
+          continue;
+        }
+      }
+      .map_err(|e| {
+        e.context(format!(
+          "cannot remove metadata from {}",
+          relative.display()
+        ))
+      })?;
+
+      if let Some((data, removed)) = scrubbed {
+        std::fs::write(&path, data)?;
+        report.scrubbed.push((relative, removed));
+      }
+    }
+  }
+
+  report.scrubbed.sort();
+  report.skipped.sort();
+
+  Ok(report)
+}
+
+/// Drops exif, xmp, iptc and comment segments, everything needed to display the image is kept.
+fn scrub_jpeg(data: &[u8]) -> anyhow::Result<Scrubbed> {
+  if !data.starts_with(&[0xFF, 0xD8]) {
+    return Err(anyhow!("not a jpeg file"));
+  }
+
+  let mut output = Vec::with_capacity(data.len());
+  output.extend_from_slice(&data[..2]);
+  let mut removed = Vec::new();
+  let mut position = 2;
+
+  loop {
+    // markers may be preceded by any number of fill bytes
+    while data.get(position..position + 2) == Some(&[0xFF, 0xFF]) {
+      position += 1;
+    }
+
+    let marker = match data.get(position..position + 2) {
+      Some([0xFF, marker]) => *marker,
+      _ => return Err(anyhow!("malformed segment at byte {position}")),
+    };
+
+    // end of image and markers without a payload
+    if marker == 0xD9 || marker == 0x01 || (0xD0..=0xD7).contains(&marker) {
+      output.extend_from_slice(&data[position..position + 2]);
+      position += 2;
+      if marker == 0xD9 {
+        break;
+      }
+      continue;
+    }
+
+    let length = match data.get(position + 2..position + 4) {
+      Some(length) => u16::from_be_bytes([length[0], length[1]]) as usize,
+      None => return Err(anyhow!("truncated segment at byte {position}")),
+    };
+    let end = position + 2 + length;
+    if length < 2 || end > data.len() {
+      return Err(anyhow!("truncated segment at byte {position}"));
+    }
+    let payload = &data[position + 4..end];
+
+    let description = match marker {
+      0xE1 if payload.starts_with(b"Exif\0") => "exif",
+      0xE1 if payload.starts_with(b"http://ns.adobe.com/") => "xmp",
+      0xE1 => "app1 segment",
+      0xED => "iptc",
+      0xFE => "comment",
+      _ => {
+        output.extend_from_slice(&data[position..end]);
+        position = end;
+
+        // the compressed image follows the start of scan and contains no further metadata
+        if marker == 0xDA {
+          output.extend_from_slice(&data[end..]);
+          break;
+        }
+        continue;
+      }
+    };
+
+    if !removed.iter().any(|entry| entry == description) {
+      removed.push(description.to_string());
+    }
+    position = end;
+  }
+
+  Ok((!removed.is_empty()).then_some((output, removed)))
+}
+
+/// Drops text, exif and timestamp chunks.
+fn scrub_png(data: &[u8]) -> anyhow::Result<Scrubbed> {
+  const SIGNATURE: &[u8] = b"\x89PNG\r\n\x1a\n";
+
+  if !data.starts_with(SIGNATURE) {
+    return Err(anyhow!("not a png file"));
+  }
+
+  let mut output = Vec::with_capacity(data.len());
+  output.extend_from_slice(SIGNATURE);
+  let mut removed = Vec::new();
+  let mut position = SIGNATURE.len();
+
+  while position < data.len() {
+    let header = data
+      .get(position..position + 8)
+      .ok_or_else(|| anyhow!("truncated chunk at byte {position}"))?;
+    let length = u32::from_be_bytes([header[0], header[1], header[2], header[3]]) as usize;
+    let chunk_type = &header[4..8];
+    // length, type, data and crc
+    let end = position + 12 + length;
+    if end > data.len() {
+      return Err(anyhow!("truncated chunk at byte {position}"));
+    }
+    let chunk_data = &data[position + 8..position + 8 + length];
+
+    match chunk_type {
+      b"tEXt" | b"zTXt" | b"iTXt" => {
+        let keyword = chunk_data.split(|byte| *byte == 0).next().unwrap_or(&[]);
+        removed.push(format!("text {}", String::from_utf8_lossy(keyword)));
+      }
+      b"eXIf" => removed.push("exif".to_string()),
+      b"tIME" => removed.push("modification time".to_string()),
+      _ => output.extend_from_slice(&data[position..end]),
+    }
+
+    position = end;
+    if chunk_type == b"IEND" {
+      break;
+    }
+  }
+
+  if position < data.len() {
+    removed.push("data after the image".to_string());
+  }
+
+  Ok((!removed.is_empty()).then_some((output, removed)))
+}
+
+/// Drops the document information dictionary and the xmp metadata of the document. Saving rewrites
+/// the whole file, so earlier revisions of incrementally updated documents are gone as well.
+fn scrub_pdf(data: &[u8]) -> anyhow::Result<Scrubbed> {
+  let mut document = Document::load_mem(data)?;
+  if document.is_encrypted() {
+    return Err(anyhow!("encrypted pdf files are not supported"));
+  }
+
+  let mut removed = Vec::new();
+
+  if let Some(info) = document.trailer.remove(b"Info") {
+    let info = match info {
+      Object::Reference(id) => document.objects.remove(&id),
+      other => Some(other),
+    };
+
+    let keys = match info.as_ref().map(Object::as_dict) {
+      Some(Ok(dictionary)) => dictionary
+        .iter()
+        .map(|(key, _)| String::from_utf8_lossy(key).into_owned())
+        .collect::<Vec<String>>(),
+      _ => Vec::new(),
+    };
+    removed.push(format!("document information ({})", keys.join(", ")));
+  }
+
+  let metadata = document
+    .catalog_mut()
+    .ok()
+    .and_then(|catalog| catalog.remove(b"Metadata"));
+  if let Some(metadata) = metadata {
+    if let Object::Reference(id) = metadata {
+      document.objects.remove(&id);
+    }
+    removed.push("xmp".to_string());
+  }
+
+  if removed.is_empty() {
+    return Ok(None);
+  }
+
+  let mut output = Vec::with_capacity(data.len());
+  document.save_to(&mut output)?;
+
+  Ok(Some((output, removed)))
+}
+
+/// Replaces the given metadata parts of a zip based office document, other parts are copied as is.
+fn scrub_zip(data: &[u8], parts: &[(&str, &str)]) -> anyhow::Result<Scrubbed> {
+  let mut archive = ZipArchive::new(Cursor::new(data))?;
+  let mut writer = ZipWriter::new(Cursor::new(Vec::with_capacity(data.len())));
+  let mut removed = Vec::new();
+
+  for index in 0..archive.len() {
+    let file = archive.by_index_raw(index)?;
+
+    match parts.iter().find(|(name, _)| *name == file.name()) {
+      Some((name, replacement)) => {
+        drop(file);
+        writer.start_file(*name, FileOptions::default())?;
+        writer.write_all(replacement.as_bytes())?;
+        removed.push(name.to_string());
+      }
+      None => writer.raw_copy_file(file)?,
+    }
+  }
+
+  if removed.is_empty() {
+    return Ok(None);
+  }
+
+  Ok(Some((writer.finish()?.into_inner(), removed)))
+}
+
+#[cfg(test)]
+mod tests {
+  use std::io::{Cursor, Read, Write};
+  use std::path::PathBuf;
+
+  use lopdf::{dictionary, Document, Object};
+  use zip::write::FileOptions;
+  use zip::{ZipArchive, ZipWriter};
+
+  use crate::service::metadata::{
+    scrub_jpeg, scrub_pdf, scrub_png, scrub_tree, scrub_zip, OOXML_PARTS,
+  };
+
+  fn segment(marker: u8, payload: &[u8]) -> Vec<u8> {
+    let mut segment = vec![0xFF, marker];
+    segment.extend_from_slice(&((payload.len() + 2) as u16).to_be_bytes());
+    segment.extend_from_slice(payload);
+    segment
+  }
+
+  #[test]
+  fn test_scrubs_jpeg() {
+    let mut jpeg = vec![0xFF, 0xD8];
+    jpeg.extend(segment(0xE0, b"JFIF\0\x01\x01"));
+    jpeg.extend(segment(0xE1, b"Exif\0\0Jane Doe"));
+    jpeg.extend(segment(0xFE, b"made by Jane Doe"));
+    jpeg.extend(segment(0xDA, b"\x01\x01\x00"));
+    jpeg.extend_from_slice(&[0x12, 0x34, 0xFF, 0xD9]);
+
+    let (scrubbed, removed) = scrub_jpeg(&jpeg).unwrap().unwrap();
+
+    assert_eq!(removed, vec!["exif", "comment"]);
+    let mut expected = vec![0xFF, 0xD8];
+    expected.extend(segment(0xE0, b"JFIF\0\x01\x01"));
+    expected.extend(segment(0xDA, b"\x01\x01\x00"));
+    expected.extend_from_slice(&[0x12, 0x34, 0xFF, 0xD9]);
+    assert_eq!(scrubbed, expected);
+
+    assert!(scrub_jpeg(&expected).unwrap().is_none());
+    assert!(scrub_jpeg(&jpeg[..10]).is_err());
+  }
+
+  #[test]
+  fn test_scrubs_png() {
+    fn chunk(chunk_type: &[u8], data: &[u8]) -> Vec<u8> {
+      let mut chunk = (data.len() as u32).to_be_bytes().to_vec();
+      chunk.extend_from_slice(chunk_type);
+      chunk.extend_from_slice(data);
+      chunk.extend_from_slice(&[0, 0, 0, 0]);
+      chunk
+    }
+
+    let mut png = b"\x89PNG\r\n\x1a\n".to_vec();
+    png.extend(chunk(b"IHDR", &[0; 13]));
+    png.extend(chunk(b"tEXt", b"Author\0Jane Doe"));
+    png.extend(chunk(b"IDAT", &[1, 2, 3]));
+    png.extend(chunk(b"IEND", &[]));
+
+    let (scrubbed, removed) = scrub_png(&png).unwrap().unwrap();
+
+    assert_eq!(removed, vec!["text Author"]);
+    assert!(!scrubbed.windows(8).any(|window| window == b"Jane Doe"));
+    assert!(scrub_png(&scrubbed).unwrap().is_none());
+  }
+
+  #[test]
+  fn test_scrubs_pdf() {
+    let mut document = Document::with_version("1.5");
+    let pages = document.new_object_id();
+    document.objects.insert(
+      pages,
+      Object::Dictionary(dictionary! { "Type" => "Pages", "Kids" => vec![], "Count" => 0 }),
+    );
+    let catalog = document.add_object(dictionary! { "Type" => "Catalog", "Pages" => pages });
+    let info = document.add_object(dictionary! {
+      "Author" => Object::string_literal("Jane Doe"),
+    });
+    document.trailer.set("Root", catalog);
+    document.trailer.set("Info", info);
+    let mut pdf = Vec::new();
+    document.save_to(&mut pdf).unwrap();
+
+    let (scrubbed, removed) = scrub_pdf(&pdf).unwrap().unwrap();
+
+    assert_eq!(removed, vec!["document information (Author)"]);
+    assert!(!scrubbed.windows(8).any(|window| window == b"Jane Doe"));
+    assert!(scrub_pdf(&scrubbed).unwrap().is_none());
+  }
+
+  #[test]
+  fn test_scrubs_office_documents() {
+    let mut writer = ZipWriter::new(Cursor::new(Vec::new()));
+    writer
+      .start_file("word/document.xml", FileOptions::default())
+      .unwrap();
+    writer.write_all(b"<document/>").unwrap();
+    writer
+      .start_file("docProps/core.xml", FileOptions::default())
+      .unwrap();
+    writer
+      .write_all(b"<cp:coreProperties><dc:creator>Jane Doe</dc:creator></cp:coreProperties>")
+      .unwrap();
+    let docx = writer.finish().unwrap().into_inner();
+
+    let (scrubbed, removed) = scrub_zip(&docx, OOXML_PARTS).unwrap().unwrap();
+
+    assert_eq!(removed, vec!["docProps/core.xml"]);
+    let mut archive = ZipArchive::new(Cursor::new(scrubbed)).unwrap();
+    let mut core = String::new();
+    archive
+      .by_name("docProps/core.xml")
+      .unwrap()
+      .read_to_string(&mut core)
+      .unwrap();
+    assert!(!core.contains("Jane Doe"));
+    let mut document = String::new();
+    archive
+      .by_name("word/document.xml")
+      .unwrap()
+      .read_to_string(&mut document)
+      .unwrap();
+    assert_eq!(document, "<document/>");
+  }
+
+  #[test]
+  fn test_skips_lfs_pointers() {
+    let dir = tempfile::tempdir().unwrap();
+    let root = dir.path();
+    std::fs::create_dir_all(root.join("figures")).unwrap();
+    std::fs::write(
+      root.join("figures/plot.png"),
+      "version https://git-lfs.github.com/spec/v1\n\
+       oid sha256:4d7a214614ab2935c943f9e0ff69d22eadbb8f32b1258daaa5e2ca24d17e2393\n\
+       size 12345\n",
+    )
+    .unwrap();
+    let mut jpeg = vec![0xFF, 0xD8];
+    jpeg.extend(segment(0xE1, b"Exif\0\0Jane Doe"));
+    jpeg.extend_from_slice(&[0xFF, 0xD9]);
+    // named like a png, but parsed as the jpeg it is
+    std::fs::write(root.join("photo.png"), &jpeg).unwrap();
+
+    let report = scrub_tree(root).unwrap();
+
+    assert_eq!(report.skipped, vec![PathBuf::from("figures/plot.png")]);
+    assert_eq!(
+      report.scrubbed,
+      vec![(PathBuf::from("photo.png"), vec!["exif".to_string()])]
+    );
+  }
+}
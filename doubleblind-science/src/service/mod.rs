@@ -1,3 +1,15 @@
+pub mod access;
+pub mod anonymize;
+pub mod config;
+pub mod custom_domain;
+pub mod delivery;
 pub mod deploy;
+pub mod domain;
+pub mod exclude;
+pub mod expiry;
+pub mod extract;
 pub mod github_app;
+pub mod metadata;
+pub mod redirects;
+pub mod reveal;
 pub mod token;
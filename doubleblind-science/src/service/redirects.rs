@@ -0,0 +1,244 @@
+use std::cmp::Reverse;
+use std::io;
+use std::path::Path;
+
+use anyhow::anyhow;
+
+/// file in the root of a site with netlify style redirect rules
+pub(crate) const REDIRECTS_FILE: &str = "_redirects";
+/// more rules are most likely not written by hand
+const MAX_RULES: usize = 1000;
+/// status codes a rule may use
+const ALLOWED_STATUS: [u16; 7] = [200, 301, 302, 303, 307, 308, 404];
+
+enum Segment {
+  Literal(String),
+  Placeholder(String),
+}
+
+struct Rule {
+  segments: Vec<Segment>,
+  splat: bool,
+  to: String,
+  status: u16,
+  force: bool,
+}
+
+/// Target of the first rule matching a request.
+#[derive(Debug, PartialEq, Eq)]
+pub(crate) struct Redirect {
+  /// path inside of the site for 200 and 404, otherwise the location the visitor is sent to
+  pub(crate) to: String,
+  pub(crate) status: u16,
+  /// applies even if a file exists at the requested path
+  pub(crate) force: bool,
+}
+
+/// Rules of a `_redirects` file, `from to [status][!]` per line like on netlify. `*` at the end of
+/// `from` is available as `:splat`, `:name` segments are placeholders.
+#[derive(Default)]
+pub(crate) struct RedirectRules {
+  rules: Vec<Rule>,
+}
+
+impl RedirectRules {
+  /// Reads the rules from the root of a site, none if it has no redirects file.
+  pub(crate) async fn load(site: &Path) -> anyhow::Result<Self> {
+    match tokio::fs::read_to_string(site.join(REDIRECTS_FILE)).await {
+      Ok(content) => Self::parse(&content),
+      Err(e) if e.kind() == io::ErrorKind::NotFound => Ok(Self::default()),
+      Err(e) => Err(anyhow!("cannot read {REDIRECTS_FILE}: {e}")),
+    }
+  }
+
+  pub(crate) fn parse(content: &str) -> anyhow::Result<Self> {
+    let mut rules = Vec::new();
+
+    for (number, line) in content.lines().enumerate() {
+      let line = line.trim();
+      if line.is_empty() || line.starts_with('#') {
+        continue;
+      }
+
+      let rule = Rule::parse(line)
+        .map_err(|e| anyhow!("invalid {REDIRECTS_FILE}: line {}: {e}", number + 1))?;
+      rules.push(rule);
+    }
+
+    if rules.len() > MAX_RULES {
+      return Err(anyhow!(
+        "invalid {REDIRECTS_FILE}: more than {MAX_RULES} rules"
+      ));
+    }
+
+    Ok(RedirectRules { rules })
+  }
+
+  pub(crate) fn len(&self) -> usize {
+    self.rules.len()
+  }
+
+  pub(crate) fn is_empty(&self) -> bool {
+    self.rules.is_empty()
+  }
+
+  /// Returns the first rule matching the path of a request.
+  pub(crate) fn find(&self, path: &str) -> Option<Redirect> {
+    let parts = split_path(path);
+
+    self.rules.iter().find_map(|rule| rule.apply(&parts))
+  }
+}
+
+impl Rule {
+  fn parse(line: &str) -> anyhow::Result<Self> {
+    let fields = line.split_whitespace().collect::<Vec<&str>>();
+    let (from, to, status) = match fields[..] {
+      [from, to] => (from, to, "301"),
+      [from, to, status] => (from, to, status),
+      [_] => return Err(anyhow!("target is missing")),
+      _ => return Err(anyhow!("conditions are not supported")),
+    };
+
+    if !from.starts_with('/') {
+      return Err(anyhow!("{from} has to start with a slash"));
+    }
+
+    let (pattern, splat) = match from.strip_suffix('*') {
+      Some(pattern) => (pattern, true),
+      None => (from, false),
+    };
+
+    let segments = split_path(pattern)
+      .into_iter()
+      .map(|segment| {
+        if segment.contains('*') {
+          return Err(anyhow!("{from} may only end with *"));
+        }
+        Ok(match segment.strip_prefix(':') {
+          Some(name) if !name.is_empty() => Segment::Placeholder(name.to_string()),
+          _ => Segment::Literal(segment.to_string()),
+        })
+      })
+      .collect::<anyhow::Result<Vec<Segment>>>()?;
+
+    let (status, force) = match status.strip_suffix('!') {
+      Some(status) => (status, true),
+      None => (status, false),
+    };
+    let status = status
+      .parse::<u16>()
+      .ok()
+      .filter(|status| ALLOWED_STATUS.contains(status))
+      .ok_or_else(|| anyhow!("status {status} is not supported"))?;
+
+    let local = to.starts_with('/');
+    if !local && !to.starts_with("https://") && !to.starts_with("http://") {
+      return Err(anyhow!("{to} has to be a path or an http url"));
+    }
+    if !local && (status == 200 || status == 404) {
+      return Err(anyhow!("proxying to {to} is not supported"));
+    }
+    if local && split_path(to).contains(&"..") {
+      return Err(anyhow!("{to} points outside of the site"));
+    }
+
+    Ok(Rule {
+      segments,
+      splat,
+      to: to.to_string(),
+      status,
+      force,
+    })
+  }
+
+  fn apply(&self, parts: &[&str]) -> Option<Redirect> {
+    if parts.len() < self.segments.len() || (!self.splat && parts.len() != self.segments.len()) {
+      return None;
+    }
+
+    let mut placeholders = Vec::new();
+    for (segment, part) in self.segments.iter().zip(parts) {
+      match segment {
+        Segment::Literal(literal) if literal == part => {}
+        Segment::Literal(_) => return None,
+        Segment::Placeholder(name) => placeholders.push((name.as_str(), *part)),
+      }
+    }
+
+    let mut to = self.to.clone();
+    if self.splat {
+      to = to.replace(":splat", &parts[self.segments.len()..].join("/"));
+    }
+    // longer names first, so `:id` does not eat the beginning of `:identifier`
+    placeholders.sort_by_key(|(name, _)| Reverse(name.len()));
+    for (name, value) in placeholders {
+      to = to.replace(&format!(":{name}"), value);
+    }
+
+    Some(Redirect {
+      to,
+      status: self.status,
+      force: self.force,
+    })
+  }
+}
+
+/// Segments of a path, ignoring empty ones so that trailing slashes do not matter.
+fn split_path(path: &str) -> Vec<&str> {
+  path
+    .split('/')
+    .filter(|segment| !segment.is_empty())
+    .collect()
+}
+
+#[cfg(test)]
+mod tests {
+  use crate::service::redirects::{Redirect, RedirectRules};
+
+  #[test]
+  fn test_matches_rules() {
+    let rules = RedirectRules::parse(
+      r#"
+      # moved pages
+      /old-results    /results
+      /docs/*         /handbook/:splat   302
+      /runs/:id/log   /logs/:id.txt      200
+      /paper          https://example.org/paper.pdf 307!
+      /*              /index.html        200
+      "#,
+    )
+    .unwrap();
+
+    let find = |path: &str| rules.find(path).unwrap();
+
+    assert_eq!(
+      find("/old-results/"),
+      Redirect {
+        to: "/results".to_string(),
+        status: 301,
+        force: false
+      }
+    );
+    assert_eq!(find("/docs/a/b.html").to, "/handbook/a/b.html");
+    assert_eq!(find("/docs").to, "/handbook/");
+    assert_eq!(find("/runs/17/log").to, "/logs/17.txt");
+    assert_eq!(find("/runs/17/log").status, 200);
+    assert!(find("/paper").force);
+    assert_eq!(find("/something/else").to, "/index.html");
+  }
+
+  #[test]
+  fn test_rejects_invalid_rules() {
+    let error = |content: &str| format!("{:#}", RedirectRules::parse(content).err().unwrap());
+
+    assert!(error("/a").contains("line 1"));
+    assert!(error("\n/a /b 418").contains("line 2"));
+    assert!(error("a /b").contains("slash"));
+    assert!(error("/a/*/b /c").contains("end with"));
+    assert!(error("/a https://example.org 200").contains("proxying"));
+    assert!(error("/a /../secret 200").contains("outside"));
+    assert!(error("/a /b 301 Country=de").contains("conditions"));
+    assert!(RedirectRules::parse("# nothing\n\n").unwrap().is_empty());
+  }
+}
@@ -0,0 +1,146 @@
+use std::path::Path;
+
+use regex::bytes::Regex;
+
+/// Address of the real repository behind a camera ready site.
+pub(crate) fn repository_url(full_name: &str) -> String {
+  format!("https://github.com/{full_name}")
+}
+
+fn escape_html(text: &str) -> String {
+  text
+    .replace('&', "&amp;")
+    .replace('<', "&lt;")
+    .replace('>', "&gt;")
+    .replace('"', "&quot;")
+}
+
+fn redirect_page(full_name: &str) -> String {
+  let url = escape_html(&repository_url(full_name));
+
+  format!(
+    "<!DOCTYPE html>\n<html>\n<head>\n<meta charset=\"utf-8\">\n\
+     <meta http-equiv=\"refresh\" content=\"0; url={url}\">\n\
+     <link rel=\"canonical\" href=\"{url}\">\n<title>Moved</title>\n</head>\n\
+     <body><a href=\"{url}\">{url}</a></body>\n</html>\n"
+  )
+}
+
+fn banner(full_name: &str) -> String {
+  let url = escape_html(&repository_url(full_name));
+
+  format!(
+    "<div style=\"padding:0.5em;background:#ffd;border-bottom:1px solid #cc9;font-family:sans-serif;\
+     text-align:center\">This artifact is no longer anonymized, the source is available at \
+     <a href=\"{url}\">{url}</a></div>"
+  )
+}
+
+/// Replaces the site with pages sending every visitor to the real repository.
+pub(crate) async fn write_redirect(root: &Path, full_name: &str) -> anyhow::Result<()> {
+  let page = redirect_page(full_name);
+
+  tokio::fs::create_dir_all(root).await?;
+  tokio::fs::write(root.join("index.html"), &page).await?;
+  tokio::fs::write(root.join("404.html"), &page).await?;
+
+  Ok(())
+}
+
+/// Adds a banner linking the real repository to the top of every html page below `root`, returns
+/// how many pages got one. Pages without a body tag are left alone, symlinks are never followed.
+pub(crate) async fn insert_banner(root: &Path, full_name: &str) -> anyhow::Result<usize> {
+  let body = Regex::new(r"(?i)<body(?:\s[^>]*)?>")?;
+  let banner = banner(full_name);
+  let mut changed = 0;
+  let mut directories = vec![root.to_path_buf()];
+
+  while let Some(directory) = directories.pop() {
+    let mut entries = tokio::fs::read_dir(&directory).await?;
+
+    while let Some(entry) = entries.next_entry().await? {
+      let file_type = entry.file_type().await?;
+      let path = entry.path();
+
+      if file_type.is_dir() {
+        directories.push(path);
+        continue;
+      }
+
+      let is_html = path
+        .extension()
+        .and_then(|extension| extension.to_str())
+        .map(|extension| {
+          extension.eq_ignore_ascii_case("html") || extension.eq_ignore_ascii_case("htm")
+        })
+        .unwrap_or(false);
+      if !file_type.is_file() || !is_html {
+        continue;
+      }
+
+      let mut content = tokio::fs::read(&path).await?;
+      let end = match body.find(&content) {
+        Some(tag) => tag.end(),
+        None => continue,
+      };
+
+      content.splice(end..end, banner.bytes());
+      tokio::fs::write(&path, &content).await?;
+      changed += 1;
+    }
+  }
+
+  Ok(changed)
+}
+
+#[cfg(test)]
+mod tests {
+  use crate::service::reveal::{insert_banner, write_redirect};
+
+  #[tokio::test]
+  async fn test_inserts_banner() {
+    let dir = tempfile::tempdir().unwrap();
+    let root = dir.path();
+    tokio::fs::create_dir_all(root.join("docs")).await.unwrap();
+    tokio::fs::write(
+      root.join("docs/index.HTML"),
+      "<html><BODY class=\"x\"><p>hi</p></BODY></html>",
+    )
+    .await
+    .unwrap();
+    tokio::fs::write(root.join("fragment.html"), "<p>no body</p>")
+      .await
+      .unwrap();
+    tokio::fs::write(root.join("notes.txt"), "<body>")
+      .await
+      .unwrap();
+
+    assert_eq!(insert_banner(root, "jdoe/paper").await.unwrap(), 1);
+
+    let page = tokio::fs::read_to_string(root.join("docs/index.HTML"))
+      .await
+      .unwrap();
+    assert!(page.starts_with("<html><BODY class=\"x\"><div"));
+    assert!(page.contains("https://github.com/jdoe/paper"));
+    assert!(page.ends_with("<p>hi</p></BODY></html>"));
+    assert_eq!(
+      tokio::fs::read_to_string(root.join("notes.txt"))
+        .await
+        .unwrap(),
+      "<body>"
+    );
+  }
+
+  #[tokio::test]
+  async fn test_writes_redirect() {
+    let dir = tempfile::tempdir().unwrap();
+    let root = dir.path();
+
+    write_redirect(root, "jdoe/paper").await.unwrap();
+
+    for page in ["index.html", "404.html"] {
+      let content = tokio::fs::read_to_string(root.join(page)).await.unwrap();
+      assert!(content.contains("url=https://github.com/jdoe/paper"));
+    }
+  }
+}
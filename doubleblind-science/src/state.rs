@@ -1,4 +1,5 @@
 use std::collections::HashMap;
+use std::net::SocketAddr;
 use std::path::Path;
 use std::sync::Arc;
 use std::time::Duration;
@@ -12,7 +13,12 @@ use uuid::Uuid;
 use migration::{Migrator, MigratorTrait};
 
 use crate::auth::SessionData;
-use crate::service::deploy::DeploymentService;
+use crate::service::access::AccessService;
+use crate::service::custom_domain::CustomDomainService;
+use crate::service::delivery::DeliveryService;
+use crate::service::deploy::{DeploymentService, SuspensionPolicy};
+use crate::service::expiry::ExpiryService;
+use crate::service::extract::ExtractionLimits;
 use crate::service::github_app::ProjectService;
 use crate::service::token::TokenService;
 
@@ -24,7 +30,12 @@ pub(crate) struct DoubleBlindState {
   pub project_service: ProjectService,
   pub token_service: TokenService,
   pub deployment_service: DeploymentService,
+  pub access_service: AccessService,
+  pub custom_domain_service: CustomDomainService,
+  pub expiry_service: ExpiryService,
+  pub delivery_service: DeliveryService,
   pub github_hmac_secret: String,
+  pub admin_token: Option<String>,
   pub repos_per_installation: Arc<RwLock<Vec<i64>>>,
 }
 
@@ -37,8 +48,15 @@ impl DoubleBlindState {
     github_client_id: &str,
     website_path: &Path,
     website_domain: &str,
+    dns_resolver: Option<SocketAddr>,
     github_hmac_secret_file: &Path,
     github_private_key_file: &Path,
+    access_secret_file: Option<&Path>,
+    admin_token_file: Option<&Path>,
+    deployment_history: u64,
+    extraction_limits: ExtractionLimits,
+    default_quota: u64,
+    suspension_policy: SuspensionPolicy,
   ) -> DoubleBlindState {
     // reading secrets from files
     let database_password = std::fs::read_to_string(password_file)
@@ -47,6 +65,25 @@ impl DoubleBlindState {
       .expect("cannot read github hmac secret file");
 
     github_hmac_secret.pop(); // remove trailing line break at the end
+    let access_secret = access_secret_file.map(|path| {
+      let secret = std::fs::read_to_string(path)
+        .expect("cannot read access secret file")
+        .trim_end()
+        .as_bytes()
+        .to_vec();
+      // an empty secret would let everybody forge cookies
+      assert!(!secret.is_empty(), "access secret file is empty");
+      secret
+    });
+    let admin_token = admin_token_file.map(|path| {
+      let token = std::fs::read_to_string(path)
+        .expect("cannot read admin token file")
+        .trim_end()
+        .to_string();
+      // an empty token would let everybody in
+      assert!(!token.is_empty(), "admin token file is empty");
+      token
+    });
 
     let mut db_options = ConnectOptions::new(format!(
       "postgresql://{}:{}@{}/{}",
@@ -70,15 +107,37 @@ impl DoubleBlindState {
       .await
       .expect("cannot run migrations");
 
+    let project_service = ProjectService::from_db(db.clone());
+    let token_service = TokenService::new(github_client_id.to_string(), github_private_key_file);
+
+    let deployment_service = DeploymentService::new(
+      db.clone(),
+      project_service.clone(),
+      token_service.clone(),
+      website_path.to_path_buf(),
+      website_domain.to_string(),
+      deployment_history,
+      extraction_limits,
+      default_quota,
+      suspension_policy,
+    );
+
     DoubleBlindState {
       sessions: Default::default(),
-      project_service: ProjectService::from_db(db.clone()),
-      deployment_service: DeploymentService::new(
-        website_path.to_path_buf(),
+      expiry_service: ExpiryService::new(db.clone(), deployment_service.clone()),
+      deployment_service,
+      access_service: AccessService::new(db.clone(), access_secret, website_domain.to_string()),
+      custom_domain_service: CustomDomainService::new(
+        db.clone(),
+        dns_resolver,
         website_domain.to_string(),
-      ),
-      token_service: TokenService::new(github_client_id.to_string(), github_private_key_file),
+      )
+        .expect("cannot configure dns resolver"),
+      delivery_service: DeliveryService::new(db.clone()),
+      project_service,
+      token_service,
       github_hmac_secret,
+      admin_token,
       repos_per_installation: Arc::new(RwLock::new(Vec::new())),
     }
   }
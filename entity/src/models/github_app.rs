@@ -9,6 +9,9 @@ pub struct Model {
   #[sea_orm(primary_key, auto_increment = false)]
   pub id: Uuid,
   pub installation_id: i64,
+  pub quota: Option<i64>,
+  pub suspended_at: Option<TimeDateTimeWithTimeZone>,
+  pub removed_at: Option<TimeDateTimeWithTimeZone>,
   pub last_update: TimeDateTimeWithTimeZone,
 }
 
@@ -2,5 +2,11 @@
 
 pub mod prelude;
 
+pub mod access_code;
+pub mod custom_domain;
+pub mod deployment;
 pub mod github_app;
+pub mod redaction_report;
+pub mod redaction_term;
 pub mod repository;
+pub mod webhook_delivery;
@@ -1,4 +1,10 @@
 //! `SeaORM` Entity. Generated by sea-orm-codegen 0.11.3
 
+pub use super::access_code::Entity as AccessCode;
+pub use super::custom_domain::Entity as CustomDomain;
+pub use super::deployment::Entity as Deployment;
 pub use super::github_app::Entity as GithubApp;
+pub use super::redaction_report::Entity as RedactionReport;
+pub use super::redaction_term::Entity as RedactionTerm;
 pub use super::repository::Entity as Repository;
+pub use super::webhook_delivery::Entity as WebhookDelivery;
@@ -3,6 +3,36 @@
 use sea_orm::entity::prelude::*;
 use serde::{Deserialize, Serialize};
 
+#[derive(Clone, Debug, PartialEq, Eq, EnumIter, DeriveActiveEnum, Serialize, Deserialize)]
+#[sea_orm(rs_type = "String", db_type = "Text")]
+#[serde(rename_all = "snake_case")]
+pub enum CameraReadyLink {
+  #[sea_orm(string_value = "banner")]
+  Banner,
+  #[sea_orm(string_value = "redirect")]
+  Redirect,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq, EnumIter, DeriveActiveEnum, Serialize, Deserialize)]
+#[sea_orm(rs_type = "String", db_type = "Text")]
+#[serde(rename_all = "snake_case")]
+pub enum ExpiryAction {
+  #[sea_orm(string_value = "unpublish")]
+  Unpublish,
+  #[sea_orm(string_value = "public")]
+  Public,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq, EnumIter, DeriveActiveEnum, Serialize, Deserialize)]
+#[sea_orm(rs_type = "String", db_type = "Text")]
+#[serde(rename_all = "snake_case")]
+pub enum SiteFallback {
+  #[sea_orm(string_value = "not_found")]
+  NotFound,
+  #[sea_orm(string_value = "spa")]
+  Spa,
+}
+
 #[derive(Clone, Debug, PartialEq, DeriveEntityModel, Eq, Serialize, Deserialize)]
 #[sea_orm(table_name = "repository")]
 pub struct Model {
@@ -13,6 +43,8 @@ pub struct Model {
   pub domain: Option<String>,
   #[sea_orm(column_type = "Text", nullable)]
   pub branch: Option<String>,
+  #[sea_orm(column_type = "Text", nullable)]
+  pub path: Option<String>,
   #[sea_orm(column_type = "Text")]
   pub github_full_name: String,
   #[sea_orm(column_type = "Text")]
@@ -20,6 +52,13 @@ pub struct Model {
   pub github_id: i64,
   pub trusted: bool,
   pub deployed: bool,
+  pub disk_usage: i64,
+  pub camera_ready: bool,
+  pub camera_ready_link: Option<CameraReadyLink>,
+  pub publish_after: Option<TimeDateTimeWithTimeZone>,
+  pub expires_at: Option<TimeDateTimeWithTimeZone>,
+  pub expiry_action: ExpiryAction,
+  pub fallback: SiteFallback,
   pub last_update: TimeDateTimeWithTimeZone,
   pub created_at: TimeDateTimeWithTimeZone,
 }
@@ -34,6 +73,38 @@ pub enum Relation {
     on_delete = "NoAction"
   )]
   GithubApp,
+  #[sea_orm(has_many = "super::access_code::Entity")]
+  AccessCode,
+  #[sea_orm(has_many = "super::custom_domain::Entity")]
+  CustomDomain,
+  #[sea_orm(has_many = "super::deployment::Entity")]
+  Deployment,
+  #[sea_orm(has_many = "super::redaction_term::Entity")]
+  RedactionTerm,
+}
+
+impl Related<super::access_code::Entity> for Entity {
+  fn to() -> RelationDef {
+    Relation::AccessCode.def()
+  }
+}
+
+impl Related<super::custom_domain::Entity> for Entity {
+  fn to() -> RelationDef {
+    Relation::CustomDomain.def()
+  }
+}
+
+impl Related<super::deployment::Entity> for Entity {
+  fn to() -> RelationDef {
+    Relation::Deployment.def()
+  }
+}
+
+impl Related<super::redaction_term::Entity> for Entity {
+  fn to() -> RelationDef {
+    Relation::RedactionTerm.def()
+  }
 }
 
 impl Related<super::github_app::Entity> for Entity {
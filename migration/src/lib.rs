@@ -1,12 +1,42 @@
 pub use sea_orm_migration::prelude::*;
 
 mod m20231010_000001_create_table;
+mod m20240312_000001_create_deployment;
+mod m20240315_000001_deployment_status;
+mod m20240320_000001_storage_quota;
+mod m20240322_000001_repository_path;
+mod m20240325_000001_redaction;
+mod m20240328_000001_unique_domain;
+mod m20240401_000001_camera_ready;
+mod m20240405_000001_access_code;
+mod m20240410_000001_expiry;
+mod m20240415_000001_site_fallback;
+mod m20240420_000001_custom_domain;
+mod m20240425_000001_installation_state;
+mod m20240501_000001_webhook_delivery;
+mod m20240505_000001_unique_github_id;
 
 pub struct Migrator;
 
 #[async_trait::async_trait]
 impl MigratorTrait for Migrator {
   fn migrations() -> Vec<Box<dyn MigrationTrait>> {
-    vec![Box::new(m20231010_000001_create_table::Migration)]
+    vec![
+      Box::new(m20231010_000001_create_table::Migration),
+      Box::new(m20240312_000001_create_deployment::Migration),
+      Box::new(m20240315_000001_deployment_status::Migration),
+      Box::new(m20240320_000001_storage_quota::Migration),
+      Box::new(m20240322_000001_repository_path::Migration),
+      Box::new(m20240325_000001_redaction::Migration),
+      Box::new(m20240328_000001_unique_domain::Migration),
+      Box::new(m20240401_000001_camera_ready::Migration),
+      Box::new(m20240405_000001_access_code::Migration),
+      Box::new(m20240410_000001_expiry::Migration),
+      Box::new(m20240415_000001_site_fallback::Migration),
+      Box::new(m20240420_000001_custom_domain::Migration),
+      Box::new(m20240425_000001_installation_state::Migration),
+      Box::new(m20240501_000001_webhook_delivery::Migration),
+      Box::new(m20240505_000001_unique_github_id::Migration),
+    ]
   }
 }
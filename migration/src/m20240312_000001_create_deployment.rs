@@ -0,0 +1,41 @@
+use sea_orm_migration::prelude::*;
+
+#[derive(DeriveMigrationName)]
+pub struct Migration;
+
+#[async_trait::async_trait]
+impl MigrationTrait for Migration {
+  async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+    let db = manager.get_connection();
+
+    db.execute_unprepared(
+      r#"
+      CREATE TABLE deployment (
+        id UUID PRIMARY KEY,
+        repository UUID NOT NULL REFERENCES repository(id) ON DELETE CASCADE,
+        commit_id TEXT NOT NULL,
+        retained BOOL NOT NULL,
+        created_at TIMESTAMPTZ NOT NULL
+      );
+
+      CREATE INDEX deployment_repository_idx ON deployment(repository, created_at);
+    "#,
+    )
+    .await?;
+
+    Ok(())
+  }
+
+  async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+    manager
+      .get_connection()
+      .execute_unprepared(
+        r#"
+        DROP TABLE deployment;
+      "#,
+      )
+      .await?;
+
+    Ok(())
+  }
+}
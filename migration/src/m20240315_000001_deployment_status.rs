@@ -0,0 +1,54 @@
+use sea_orm_migration::prelude::*;
+
+#[derive(DeriveMigrationName)]
+pub struct Migration;
+
+#[async_trait::async_trait]
+impl MigrationTrait for Migration {
+  async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+    let db = manager.get_connection();
+
+    // existing rows are releases which have been deployed successfully
+    db.execute_unprepared(
+      r#"
+      ALTER TABLE deployment
+        ADD COLUMN status TEXT NOT NULL DEFAULT 'succeeded',
+        ADD COLUMN "trigger" TEXT NOT NULL DEFAULT 'webhook',
+        ADD COLUMN release UUID,
+        ADD COLUMN error TEXT,
+        ADD COLUMN started_at TIMESTAMPTZ,
+        ADD COLUMN finished_at TIMESTAMPTZ;
+
+      UPDATE deployment SET release = id, started_at = created_at, finished_at = created_at;
+
+      ALTER TABLE deployment
+        ALTER COLUMN status DROP DEFAULT,
+        ALTER COLUMN "trigger" DROP DEFAULT;
+    "#,
+    )
+    .await?;
+
+    Ok(())
+  }
+
+  async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+    manager
+      .get_connection()
+      .execute_unprepared(
+        r#"
+        DELETE FROM deployment WHERE release IS NULL OR release <> id;
+
+        ALTER TABLE deployment
+          DROP COLUMN status,
+          DROP COLUMN "trigger",
+          DROP COLUMN release,
+          DROP COLUMN error,
+          DROP COLUMN started_at,
+          DROP COLUMN finished_at;
+      "#,
+      )
+      .await?;
+
+    Ok(())
+  }
+}
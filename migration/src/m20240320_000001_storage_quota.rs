@@ -0,0 +1,37 @@
+use sea_orm_migration::prelude::*;
+
+#[derive(DeriveMigrationName)]
+pub struct Migration;
+
+#[async_trait::async_trait]
+impl MigrationTrait for Migration {
+  async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+    let db = manager.get_connection();
+
+    db.execute_unprepared(
+      r#"
+      ALTER TABLE github_app ADD COLUMN quota BIGINT;
+      ALTER TABLE repository ADD COLUMN disk_usage BIGINT NOT NULL DEFAULT 0;
+      ALTER TABLE deployment ADD COLUMN size BIGINT;
+    "#,
+    )
+    .await?;
+
+    Ok(())
+  }
+
+  async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+    manager
+      .get_connection()
+      .execute_unprepared(
+        r#"
+        ALTER TABLE github_app DROP COLUMN quota;
+        ALTER TABLE repository DROP COLUMN disk_usage;
+        ALTER TABLE deployment DROP COLUMN size;
+      "#,
+      )
+      .await?;
+
+    Ok(())
+  }
+}
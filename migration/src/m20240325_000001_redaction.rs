@@ -0,0 +1,50 @@
+use sea_orm_migration::prelude::*;
+
+#[derive(DeriveMigrationName)]
+pub struct Migration;
+
+#[async_trait::async_trait]
+impl MigrationTrait for Migration {
+  async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+    let db = manager.get_connection();
+
+    db.execute_unprepared(
+      r#"
+      CREATE TABLE redaction_term (
+        id UUID PRIMARY KEY,
+        repository UUID NOT NULL REFERENCES repository(id) ON DELETE CASCADE,
+        term TEXT NOT NULL,
+        created_at TIMESTAMPTZ NOT NULL
+      );
+
+      CREATE UNIQUE INDEX redaction_term_repository_idx ON redaction_term(repository, lower(term));
+
+      CREATE TABLE redaction_report (
+        id UUID PRIMARY KEY,
+        deployment UUID NOT NULL REFERENCES deployment(id) ON DELETE CASCADE,
+        path TEXT NOT NULL,
+        replacements INT NOT NULL
+      );
+
+      CREATE INDEX redaction_report_deployment_idx ON redaction_report(deployment);
+    "#,
+    )
+    .await?;
+
+    Ok(())
+  }
+
+  async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+    manager
+      .get_connection()
+      .execute_unprepared(
+        r#"
+        DROP TABLE redaction_report;
+        DROP TABLE redaction_term;
+      "#,
+      )
+      .await?;
+
+    Ok(())
+  }
+}
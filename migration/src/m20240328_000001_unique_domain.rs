@@ -0,0 +1,62 @@
+use sea_orm_migration::prelude::*;
+use sea_orm_migration::sea_orm::Statement;
+use tracing::warn;
+
+#[derive(DeriveMigrationName)]
+pub struct Migration;
+
+/// Every repository which claimed a domain after another one, together with the first claim.
+const CLAIMS: &str = r#"
+  SELECT id, github_full_name, domain, first_value(github_full_name) OVER (
+      PARTITION BY lower(domain) ORDER BY created_at, id
+    ) AS winner,
+    row_number() OVER (PARTITION BY lower(domain) ORDER BY created_at, id) AS claim
+  FROM repository
+  WHERE domain IS NOT NULL
+"#;
+
+#[async_trait::async_trait]
+impl MigrationTrait for Migration {
+  async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+    let db = manager.get_connection();
+
+    let losers = db
+      .query_all(Statement::from_string(
+        manager.get_database_backend(),
+        format!("SELECT github_full_name, domain, winner FROM ({CLAIMS}) claims WHERE claim > 1"),
+      ))
+      .await?;
+
+    // their files may still be what is served under the domain, until the winner deploys again
+    for row in losers {
+      warn!(
+        "Repository {} loses domain {} to {}, which has to be deployed again",
+        row.try_get::<String>("", "github_full_name")?,
+        row.try_get::<String>("", "domain")?,
+        row.try_get::<String>("", "winner")?,
+      );
+    }
+
+    // repositories which claimed a domain after another one lose it and have to pick a new one
+    db.execute_unprepared(&format!(
+      r#"
+      UPDATE repository SET domain = NULL, deployed = false
+      WHERE id IN (SELECT id FROM ({CLAIMS}) claims WHERE claim > 1);
+
+      CREATE UNIQUE INDEX repository_domain_idx ON repository(lower(domain));
+    "#
+    ))
+    .await?;
+
+    Ok(())
+  }
+
+  async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+    manager
+      .get_connection()
+      .execute_unprepared("DROP INDEX repository_domain_idx;")
+      .await?;
+
+    Ok(())
+  }
+}
@@ -0,0 +1,41 @@
+use sea_orm_migration::prelude::*;
+
+#[derive(DeriveMigrationName)]
+pub struct Migration;
+
+#[async_trait::async_trait]
+impl MigrationTrait for Migration {
+  async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+    let db = manager.get_connection();
+
+    // every release published so far went through the anonymization
+    db.execute_unprepared(
+      r#"
+      ALTER TABLE repository
+        ADD COLUMN camera_ready BOOL NOT NULL DEFAULT false,
+        ADD COLUMN camera_ready_link TEXT;
+
+      ALTER TABLE deployment ADD COLUMN anonymized BOOL;
+
+      UPDATE deployment SET anonymized = true WHERE release IS NOT NULL;
+    "#,
+    )
+    .await?;
+
+    Ok(())
+  }
+
+  async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+    manager
+      .get_connection()
+      .execute_unprepared(
+        r#"
+        ALTER TABLE repository DROP COLUMN camera_ready, DROP COLUMN camera_ready_link;
+        ALTER TABLE deployment DROP COLUMN anonymized;
+      "#,
+      )
+      .await?;
+
+    Ok(())
+  }
+}
@@ -0,0 +1,37 @@
+use sea_orm_migration::prelude::*;
+
+#[derive(DeriveMigrationName)]
+pub struct Migration;
+
+#[async_trait::async_trait]
+impl MigrationTrait for Migration {
+  async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+    manager
+      .get_connection()
+      .execute_unprepared(
+        r#"
+        CREATE TABLE access_code (
+          id UUID PRIMARY KEY,
+          repository UUID NOT NULL REFERENCES repository(id) ON DELETE CASCADE,
+          label TEXT NOT NULL,
+          code_hash TEXT NOT NULL,
+          created_at TIMESTAMPTZ NOT NULL
+        );
+
+        CREATE INDEX access_code_repository_idx ON access_code(repository);
+      "#,
+      )
+      .await?;
+
+    Ok(())
+  }
+
+  async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+    manager
+      .get_connection()
+      .execute_unprepared("DROP TABLE access_code;")
+      .await?;
+
+    Ok(())
+  }
+}
@@ -0,0 +1,39 @@
+use sea_orm_migration::prelude::*;
+
+#[derive(DeriveMigrationName)]
+pub struct Migration;
+
+#[async_trait::async_trait]
+impl MigrationTrait for Migration {
+  async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+    manager
+      .get_connection()
+      .execute_unprepared(
+        r#"
+        ALTER TABLE repository
+          ADD COLUMN publish_after TIMESTAMPTZ,
+          ADD COLUMN expires_at TIMESTAMPTZ,
+          ADD COLUMN expiry_action TEXT NOT NULL DEFAULT 'unpublish';
+      "#,
+      )
+      .await?;
+
+    Ok(())
+  }
+
+  async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+    manager
+      .get_connection()
+      .execute_unprepared(
+        r#"
+        ALTER TABLE repository
+          DROP COLUMN publish_after,
+          DROP COLUMN expires_at,
+          DROP COLUMN expiry_action;
+      "#,
+      )
+      .await?;
+
+    Ok(())
+  }
+}
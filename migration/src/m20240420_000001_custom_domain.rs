@@ -0,0 +1,41 @@
+use sea_orm_migration::prelude::*;
+
+#[derive(DeriveMigrationName)]
+pub struct Migration;
+
+#[async_trait::async_trait]
+impl MigrationTrait for Migration {
+  async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+    manager
+      .get_connection()
+      .execute_unprepared(
+        r#"
+        CREATE TABLE custom_domain (
+          id UUID PRIMARY KEY,
+          repository UUID NOT NULL REFERENCES repository(id) ON DELETE CASCADE,
+          hostname TEXT NOT NULL,
+          token TEXT NOT NULL,
+          verified_at TIMESTAMPTZ,
+          created_at TIMESTAMPTZ NOT NULL,
+          UNIQUE (repository, hostname)
+        );
+
+        -- anyone may claim a hostname, but only one repository can prove that it owns it
+        CREATE UNIQUE INDEX custom_domain_verified_idx ON custom_domain(hostname)
+          WHERE verified_at IS NOT NULL;
+      "#,
+      )
+      .await?;
+
+    Ok(())
+  }
+
+  async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+    manager
+      .get_connection()
+      .execute_unprepared("DROP TABLE custom_domain;")
+      .await?;
+
+    Ok(())
+  }
+}
@@ -0,0 +1,37 @@
+use sea_orm_migration::prelude::*;
+
+#[derive(DeriveMigrationName)]
+pub struct Migration;
+
+#[async_trait::async_trait]
+impl MigrationTrait for Migration {
+  async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+    manager
+      .get_connection()
+      .execute_unprepared(
+        r#"
+        ALTER TABLE github_app
+          ADD COLUMN suspended_at TIMESTAMPTZ,
+          ADD COLUMN removed_at TIMESTAMPTZ;
+      "#,
+      )
+      .await?;
+
+    Ok(())
+  }
+
+  async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+    manager
+      .get_connection()
+      .execute_unprepared(
+        r#"
+        ALTER TABLE github_app
+          DROP COLUMN suspended_at,
+          DROP COLUMN removed_at;
+      "#,
+      )
+      .await?;
+
+    Ok(())
+  }
+}
@@ -0,0 +1,42 @@
+use sea_orm_migration::prelude::*;
+
+#[derive(DeriveMigrationName)]
+pub struct Migration;
+
+#[async_trait::async_trait]
+impl MigrationTrait for Migration {
+  async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+    manager
+      .get_connection()
+      .execute_unprepared(
+        r#"
+        CREATE TABLE webhook_delivery (
+          id TEXT PRIMARY KEY,
+          event TEXT NOT NULL,
+          action TEXT,
+          -- status code the delivery was answered with, unset while it is processed
+          status INTEGER,
+          attempts INTEGER NOT NULL DEFAULT 1,
+          duplicates INTEGER NOT NULL DEFAULT 0,
+          received_at TIMESTAMPTZ NOT NULL,
+          claimed_at TIMESTAMPTZ NOT NULL,
+          finished_at TIMESTAMPTZ
+        );
+
+        CREATE INDEX webhook_delivery_received_idx ON webhook_delivery(received_at);
+      "#,
+      )
+      .await?;
+
+    Ok(())
+  }
+
+  async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+    manager
+      .get_connection()
+      .execute_unprepared("DROP TABLE webhook_delivery;")
+      .await?;
+
+    Ok(())
+  }
+}
@@ -0,0 +1,83 @@
+use sea_orm_migration::prelude::*;
+use sea_orm_migration::sea_orm::Statement;
+use tracing::info;
+
+#[derive(DeriveMigrationName)]
+pub struct Migration;
+
+/// Reinstalling the github app added every repository a second time. The row which carries the site
+/// is kept, so its domain, releases and custom domains survive, the others are removed.
+const RANKED: &str = r#"
+  SELECT repository.id, repository.github_id, repository.github_full_name, repository.domain,
+    repository.deployed, row_number() OVER (
+      PARTITION BY repository.github_id
+      ORDER BY repository.deployed DESC, repository.domain IS NOT NULL DESC,
+        github_app.removed_at IS NULL DESC, repository.created_at DESC, repository.id
+    ) AS row
+  FROM repository
+  JOIN github_app ON github_app.id = repository.github_app
+"#;
+
+#[async_trait::async_trait]
+impl MigrationTrait for Migration {
+  async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+    let db = manager.get_connection();
+
+    let removed = db
+      .query_all(Statement::from_string(
+        manager.get_database_backend(),
+        format!(
+          "SELECT id::text AS id, github_full_name, domain, deployed FROM ({RANKED}) ranked \
+           WHERE row > 1"
+        ),
+      ))
+      .await?;
+
+    for row in removed {
+      info!(
+        "Removing duplicate repository {} {} (domain {:?}, deployed {})",
+        row.try_get::<String>("", "github_full_name")?,
+        row.try_get::<String>("", "id")?,
+        row.try_get::<Option<String>>("", "domain")?,
+        row.try_get::<bool>("", "deployed")?,
+      );
+    }
+
+    // the kept row moves over to the current installation with the current names of the repository
+    db.execute_unprepared(&format!(
+      r#"
+      UPDATE repository
+      SET github_app = current.github_app,
+        github_short_name = current.github_short_name,
+        github_full_name = current.github_full_name
+      FROM (
+        SELECT DISTINCT ON (repository.github_id) repository.github_id, repository.github_app,
+          repository.github_short_name, repository.github_full_name
+        FROM repository
+        JOIN github_app ON github_app.id = repository.github_app
+        ORDER BY repository.github_id, github_app.removed_at IS NULL DESC,
+          repository.created_at DESC, repository.id
+      ) current
+      WHERE repository.github_id = current.github_id
+        AND repository.id IN (SELECT id FROM ({RANKED}) ranked WHERE row = 1);
+
+      DELETE FROM repository
+      WHERE id IN (SELECT id FROM ({RANKED}) ranked WHERE row > 1);
+
+      CREATE UNIQUE INDEX repository_github_id_idx ON repository(github_id);
+    "#
+    ))
+    .await?;
+
+    Ok(())
+  }
+
+  async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+    manager
+      .get_connection()
+      .execute_unprepared("DROP INDEX repository_github_id_idx;")
+      .await?;
+
+    Ok(())
+  }
+}
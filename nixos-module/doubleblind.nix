@@ -25,6 +25,23 @@ in
           '';
         };
        };
+    staticHttp = {
+      enable = mkOption {
+        type = types.bool;
+        default = false;
+        description = ''serve the deployed websites from doubleblind itself instead of a separate web server'';
+      };
+      host = mkOption {
+        type = types.str;
+        default = "127.0.0.1";
+        description = ''ip address the websites are served on'';
+      };
+      port = mkOption {
+        type = types.port;
+        default = 8081;
+        description = ''port the websites are served on'';
+      };
+    };
     database = {
       host = mkOption {
         type = types.str;
@@ -81,18 +98,86 @@ in
         };
     };
 
+    accessSecretFile = mkOption {
+      type = types.nullOr (types.either types.path types.string);
+      default = null;
+      description = ''file with a random secret used to sign the cookies of reviewers who entered an access code, access codes are disabled if unset'';
+    };
+
+    nginx = {
+      enable = mkOption {
+        type = types.bool;
+        default = false;
+        description = ''serve the deployed websites with nginx, which asks doubleblind before every request whether the visitor may see the site'';
+      };
+    };
+
+    adminTokenFile = mkOption {
+      type = types.nullOr (types.either types.path types.string);
+      default = null;
+      description = ''file with the bearer token for the admin endpoints, they are disabled if unset'';
+    };
+
     domain = mkOption {
       type = types.str;
       default = "doubleblind.science";
       description = ''domain under which the websites will be hosted'';
     };
 
+    dnsResolver = mkOption {
+      type = types.nullOr types.str;
+      default = null;
+      example = "127.0.0.1:53";
+      description = ''name server used to verify custom domains, the system resolvers are used if unset'';
+    };
+
     storageLocation =  mkOption {
       type = types.either types.path types.string;
       default = "/var/lib/doubleblind/sites/";
       description = ''place where the webpages should be dropped'';
     };
 
+    deploymentHistory = mkOption {
+      type = types.int;
+      default = 5;
+      description = ''number of deployed commits per repository kept for rollbacks'';
+    };
+
+    suspensionPolicy = mkOption {
+      type = types.enum [ "unpublish" "freeze" ];
+      default = "unpublish";
+      description = ''what happens to the websites of a suspended or uninstalled github app, unpublished websites of suspended installations come back once they are unsuspended'';
+    };
+
+    deploymentWorkers = mkOption {
+      type = types.int;
+      default = 4;
+      description = ''number of deployments which are processed at the same time'';
+    };
+
+    limits = {
+      siteSize = mkOption {
+        type = types.int;
+        default = 1073741824;
+        description = ''maximum uncompressed size of a repository in bytes'';
+      };
+      siteFiles = mkOption {
+        type = types.int;
+        default = 100000;
+        description = ''maximum number of files in a repository'';
+      };
+      fileSize = mkOption {
+        type = types.int;
+        default = 134217728;
+        description = ''maximum size of a single file in bytes'';
+      };
+      quota = mkOption {
+        type = types.int;
+        default = 2147483648;
+        description = ''default disk quota per repository in bytes, including retained releases'';
+      };
+    };
+
     user = mkOption {
       type = types.str;
       default = "doubleblind";
@@ -111,6 +196,14 @@ in
   };
 
   config = lib.mkIf cfg.enable {
+    assertions = [
+      {
+        # access codes are only checked by doubleblind itself or by nginx asking it
+        assertion = cfg.accessSecretFile == null || cfg.staticHttp.enable || cfg.nginx.enable;
+        message = "dresden-zone.doubleblind.accessSecretFile requires staticHttp.enable or nginx.enable";
+      }
+    ];
+
     systemd = {
       services = {
         "doubleblind" = {
@@ -121,7 +214,15 @@ in
             exec ${pkgs.doubleblind-backend}/bin/doubeblind-science&
           '';
 
-          environment = {
+          environment = lib.optionalAttrs cfg.staticHttp.enable {
+            "DOUBLEBLIND_STATIC_LISTEN_ADDR" = "${cfg.staticHttp.host}:${toString cfg.staticHttp.port}";
+          } // lib.optionalAttrs (cfg.dnsResolver != null) {
+            "DOUBLEBLIND_DNS_RESOLVER" = cfg.dnsResolver;
+          } // lib.optionalAttrs (cfg.accessSecretFile != null) {
+            "DOUBLEBLIND_ACCESS_SECRET_PATH" = "${cfg.accessSecretFile}";
+          } // lib.optionalAttrs (cfg.adminTokenFile != null) {
+            "DOUBLEBLIND_ADMIN_TOKEN_PATH" = "${cfg.adminTokenFile}";
+          } // {
             "RUST_LOG" = "${cfg.log_level}";
             "RUST_BACKTRACE" = if (cfg.log_level == "info") then "0" else "1";
             "DOUBLEBLIND_LISTEN_ADDR" = "${cfg.http.host}:${toString cfg.http.port}";
@@ -135,6 +236,13 @@ in
             "DOUBLEBLIND_WEBSITE_DOMAIN" = "${cfg.domain}";
             "DOUBLEBLIND_GITHUB_HMAC_SECRET_PATH" = "${cfg.github.passwordFileHMACSecret}";
             "DOUBLEBLIND_GITHUB_PRIVATE_KEY_PATH" = "${cfg.github.privateKeyFile}";
+            "DOUBLEBLIND_DEPLOYMENT_HISTORY" = "${toString cfg.deploymentHistory}";
+            "DOUBLEBLIND_DEPLOYMENT_WORKERS" = "${toString cfg.deploymentWorkers}";
+            "DOUBLEBLIND_SUSPENSION_POLICY" = "${cfg.suspensionPolicy}";
+            "DOUBLEBLIND_MAX_SITE_SIZE" = "${toString cfg.limits.siteSize}";
+            "DOUBLEBLIND_MAX_SITE_FILES" = "${toString cfg.limits.siteFiles}";
+            "DOUBLEBLIND_MAX_FILE_SIZE" = "${toString cfg.limits.fileSize}";
+            "DOUBLEBLIND_DEFAULT_QUOTA" = "${toString cfg.limits.quota}";
           };
 
           serviceConfig = {
@@ -160,6 +268,49 @@ in
       };
     };
 
+    services.nginx = lib.mkIf cfg.nginx.enable {
+      enable = true;
+      virtualHosts."doubleblind-sites" = {
+        serverName = "*.${cfg.domain}";
+        # custom domains of the sites can be anything, so they end up at the default server
+        default = true;
+        root = "${cfg.storageLocation}/$host";
+        extraConfig = ''
+          # protected sites show the form for entering an access code
+          error_page 401 /.doubleblind/access;
+          # unknown sites and sites which are not published yet
+          error_page 403 =404 @doubleblind_missing;
+        '';
+        locations = {
+          "/" = {
+            extraConfig = ''
+              auth_request /.doubleblind/access/check;
+            '';
+          };
+          "= /.doubleblind/access/check" = {
+            extraConfig = ''
+              internal;
+              proxy_pass http://${cfg.http.host}:${toString cfg.http.port};
+              proxy_pass_request_body off;
+              proxy_set_header Content-Length "";
+              proxy_set_header Host $host;
+            '';
+          };
+          "= /.doubleblind/access" = {
+            extraConfig = ''
+              proxy_pass http://${cfg.http.host}:${toString cfg.http.port};
+              proxy_set_header Host $host;
+            '';
+          };
+          "@doubleblind_missing" = {
+            extraConfig = ''
+              return 404;
+            '';
+          };
+        };
+      };
+    };
+
     # user accounts for systemd units
     users.users."${cfg.user}" = {
       name = "${cfg.user}";
@@ -171,7 +322,8 @@ in
 
     users.groups."${cfg.group}" = {
       name = "doubleblind";
-      members = [ cfg.user ];
+      # nginx reads the websites directly
+      members = [ cfg.user ] ++ lib.optional cfg.nginx.enable config.services.nginx.user;
     };
   };
 }